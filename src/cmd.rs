@@ -1,5 +1,9 @@
 use clap::{Args, Parser, Subcommand};
+use git_trans::output::Format;
+use glob::Pattern;
+use std::convert::Infallible;
 use std::path::PathBuf;
+use std::str::FromStr;
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
@@ -7,29 +11,41 @@ use std::path::PathBuf;
 pub struct Cli {
     #[command(subcommand)]
     pub command: Commands,
+    /// Output format of show, todo, ls, info and history
+    #[arg(long, global = true, value_enum, default_value_t = Format::Text)]
+    pub format: Format,
+    /// Language to work on, the default language of the records if not given
+    #[arg(long = "lang", id = "select_lang", value_name = "LANG", global = true)]
+    pub lang: Option<String>,
 }
 
 #[derive(Subcommand)]
 pub enum Commands {
-    /// Init .trans folder
+    /// Init .trans folder, or add another language to an initialized one
     #[command(arg_required_else_help = true)]
     Init {
         /// Language of the project
         #[arg(required = true)]
         lang: String,
-        /// Lock track revision
+        /// Revision to track, only for a new project
         #[arg(default_value = "HEAD")]
         tag: String,
     },
     /// Add files to .trans
     #[command(arg_required_else_help = true)]
     Add {
-        /// Files to add to the records
+        /// Files or directories to add to the records
         #[arg(required = true)]
         path_args: Vec<PathBuf>,
         /// Lock files
         #[arg(long)]
         lock: bool,
+        /// Only add files matching the glob pattern, can be repeated
+        #[arg(long)]
+        include: Vec<Pattern>,
+        /// Skip files matching the glob pattern, can be repeated
+        #[arg(long)]
+        exclude: Vec<Pattern>,
     },
     /// Remove files from .trans
     #[command(arg_required_else_help = true)]
@@ -47,20 +63,49 @@ pub enum Commands {
         #[arg(short, long)]
         recursive: bool,
         /// List all files recorded
-        #[arg(short, long, exclusive = true)]
+        #[arg(short, long, conflicts_with_all = ["path", "recursive"])]
         all: bool,
     },
-    /// Show trans, review, and unsynced files
-    Todo,
+    /// Show all information of a file
+    #[command(arg_required_else_help = true)]
+    Info {
+        /// File to show information of
+        #[arg(required = true)]
+        path: PathBuf,
+    },
+    /// Show files in the todo states of the workflow, unsynced and orphaned files
+    Todo {
+        /// Only show files assigned to the git user, or to review by them
+        #[arg(long)]
+        mine: bool,
+        /// Show the files of all languages with a summary of each
+        #[arg(long, conflicts_with = "select_lang")]
+        all_langs: bool,
+    },
     /// Diff file changes
     #[command(arg_required_else_help = true)]
     Diff {
         /// Generate diff files
         #[arg(short, long)]
         gendiff: bool,
-        /// File to diff with latest revision
-        #[arg(required = true)]
+        /// Annotate each hunk with the corresponding lines of the translation
+        #[arg(short, long, conflicts_with_all = ["word_diff", "ignore_rewrap"])]
+        mapped: bool,
+        /// Diff by words instead of lines
+        #[arg(long, conflicts_with = "side_by_side")]
+        word_diff: bool,
+        /// Show the old and new source in two columns
+        #[arg(short = 'y', long)]
+        side_by_side: bool,
+        /// Ignore paragraphs reflowed upstream by joining their soft line breaks
+        #[arg(long)]
+        ignore_rewrap: bool,
+        /// Files to diff with latest revision
+        #[arg(required_unless_present = "all_unsynced")]
         path_args: Vec<PathBuf>,
+        /// Diff all unsynced files
+        #[arg(short, long, conflicts_with = "path_args")]
+        all_unsynced: bool,
     },
 
     /// Sync file with latest revision
@@ -69,55 +114,148 @@ pub enum Commands {
         /// Files to sync with latest revision
         #[arg(required = true)]
         path_args: Vec<PathBuf>,
+        /// Merge the upstream changes into the translations, changed prose is left
+        /// between conflict markers
+        #[arg(short, long)]
+        merge: bool,
     },
     /// Update sync status for all files
-    Update,
+    Update {
+        /// Move records and copies of files renamed upstream to the new path
+        #[arg(long)]
+        follow_renames: bool,
+    },
+    /// Assign a user to translate files
+    #[command(arg_required_else_help = true)]
+    Assign {
+        /// User to assign, like the email of `git config user.email`
+        #[arg(required = true)]
+        user: String,
+        /// Files to assign
+        #[arg(required = true)]
+        path_args: Vec<PathBuf>,
+        /// Assign the user to review the files instead
+        #[arg(short, long)]
+        reviewer: bool,
+        /// Message kept in the history of the files
+        #[arg(short, long)]
+        message: Option<String>,
+    },
     /// Lock files in the records
     Lock {
         /// Files to lock
         #[arg(required = true)]
         path_args: Vec<PathBuf>,
+        /// Message kept in the history of the files
+        #[arg(short, long)]
+        message: Option<String>,
     },
     /// Unlock files in the records
     Unlock {
         /// Files to unlock
         #[arg(required = true)]
         path_args: Vec<PathBuf>,
+        /// Message kept in the history of the files
+        #[arg(short, long)]
+        message: Option<String>,
+    },
+    /// Cover the translations of the recorded files into the repo folder
+    Cover {
+        /// Only cover files in this state of the workflow or a later one
+        #[arg(long, value_name = "STATE")]
+        min_progress: Option<String>,
+        /// List the files that would change without copying them
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+        /// Cover into a detached git worktree at the tracked revision instead
+        #[arg(long, group = "target")]
+        worktree: Option<PathBuf>,
+        /// Export the tracked revision into a directory and cover into it instead
+        #[arg(long, group = "target")]
+        out: Option<PathBuf>,
+        /// Revision to check out in the worktree or export, the tracked revision by default
+        #[arg(long, requires = "target")]
+        rev: Option<String>,
+    },
+    /// Reset the files covered into the repo folder to the latest revision
+    Reset {
+        /// List the files that would be reset without resetting them
+        #[arg(short = 'n', long)]
+        dry_run: bool,
     },
-    /// Cover the .trans folder into the repo folder
-    Cover,
-    /// Reset the root folder to the latest revision
-    Reset,
+    /// Cover the translations, run a build command in the repo folder and reset
+    #[command(arg_required_else_help = true)]
+    Build {
+        /// Command to run after covering, like `-- make html`
+        #[arg(required = true, last = true)]
+        command: Vec<String>,
+    },
+    /// Manage the git hooks of git-trans
+    #[command(arg_required_else_help = true)]
+    Hooks {
+        #[command(subcommand)]
+        action: HooksAction,
+    },
+    /// Check the staged changes only touch .trans, run by the pre-commit hook
+    CheckCommit,
     /// Show logs in the .trans folder
     Log,
     /// Show files in a given status
     #[command(arg_required_else_help = true)]
     Show {
-        #[command(subcommand)]
+        /// all, synced, unsynced, orphaned, locked, unlocked, or a state of the workflow
+        #[arg(required = true)]
         status: ShowStatus,
+        /// Show the files of all languages with a summary of each
+        #[arg(long, conflicts_with = "select_lang")]
+        all_langs: bool,
     },
     /// Mark files as given status
     #[command(arg_required_else_help = true)]
     Mark {
-        #[command(subcommand)]
-        status: MarkProgress,
+        /// State of the workflow to mark the files as, like trans, review or done
+        #[arg(required = true)]
+        state: String,
+        /// Files to mark
+        #[arg(required = true)]
+        path_args: Vec<PathBuf>,
+        /// Message kept in the history of the files
+        #[arg(short, long)]
+        message: Option<String>,
+    },
+    /// Show the progress, lock, sync and assignment history of a file
+    #[command(arg_required_else_help = true)]
+    History {
+        /// File to show the history of
+        #[arg(required = true)]
+        path: PathBuf,
+    },
+    /// Show word, character and line counts by progress, the completion and the unsynced backlog
+    Stats {
+        /// Number of leading components of the directories to group files by
+        #[arg(long, default_value_t = 1)]
+        depth: usize,
+        /// Show the statistics of all languages
+        #[arg(long, conflicts_with = "select_lang")]
+        all_langs: bool,
     },
 }
 
-#[derive(Subcommand)]
+/// Status of the files to show
+#[derive(Clone)]
 pub enum ShowStatus {
     /// Show all files
     All,
-    /// Show todo files
-    Trans,
-    /// Show review files
-    Review,
-    /// Show done files
-    Done,
+    /// Show files in a state of the workflow
+    State(String),
     /// Show synced files
     Synced,
     /// Show unsynced files
     Unsynced,
+    /// Show files deleted or renamed upstream
+    Orphaned,
+    /// Show files in states unknown to the workflow
+    Unknown,
     /// Show locked files
     Locked,
     /// Show unlocked files
@@ -125,13 +263,33 @@ pub enum ShowStatus {
 }
 
 #[derive(Subcommand)]
-pub enum MarkProgress {
-    /// Mark file as translate
-    Trans { path: PathBuf },
-    /// Mark file as review
-    Review { path: PathBuf },
-    /// Mark file as done
-    Done { path: PathBuf },
+pub enum HooksAction {
+    /// Install a pre-commit hook running `git trans check-commit`
+    Install {
+        /// Replace an existing pre-commit hook
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Remove the pre-commit hook installed by git-trans
+    Uninstall,
+}
+
+impl FromStr for ShowStatus {
+    type Err = Infallible;
+
+    /// Any other name is a state, checked against the workflow once it is loaded
+    fn from_str(input: &str) -> Result<ShowStatus, Self::Err> {
+        Ok(match input.to_lowercase().as_str() {
+            "all" => ShowStatus::All,
+            "synced" => ShowStatus::Synced,
+            "unsynced" => ShowStatus::Unsynced,
+            "orphaned" => ShowStatus::Orphaned,
+            "unknown" => ShowStatus::Unknown,
+            "locked" => ShowStatus::Locked,
+            "unlocked" => ShowStatus::Unlocked,
+            _ => ShowStatus::State(input.to_string()),
+        })
+    }
 }
 
 #[derive(Args)]
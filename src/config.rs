@@ -0,0 +1,414 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+use toml::{Table, Value};
+
+use crate::errors::{GitTransError, Result};
+use crate::records::Progress;
+
+/// Prefix of the environment variables overriding the configuration
+const ENV_PREFIX: &str = "GIT_TRANS_";
+
+/// Default name of the translation directory
+pub const DEFAULT_TRANS_DIR: &str = ".trans";
+
+/// Configuration file name, in the translation directory and in the user config directory
+pub const CONFIG_FILE: &str = "config.toml";
+
+/// Configuration structure, every field falls back to its default
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Config {
+    /// Name of the translation directory in the root directory
+    pub trans_dir: String,
+    /// Name of the records file in the translation directory
+    pub records_file: String,
+    /// Lock new files by default
+    pub lock: bool,
+    /// Colour output
+    pub color: ColorMode,
+    /// Default include patterns for adding directories
+    pub include: Vec<String>,
+    /// Progress of new files, the first state of the workflow by default
+    pub progress: Option<Progress>,
+    /// States of the translation workflow, ordered from the first to the last
+    pub workflow: Vec<State>,
+    /// How cover copies the translations into the root directory
+    pub cover: CoverStrategy,
+}
+
+/// A state of the translation workflow
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct State {
+    /// Name of the state, like `Trans`
+    pub name: String,
+    /// Colour of the state in text output, like `red`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    /// Letter of the state in status columns, the first letter of the name
+    /// not taken by another state if not given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub letter: Option<char>,
+    /// Files in the state are listed by `todo`
+    #[serde(default)]
+    pub todo: bool,
+    /// Files in the state wait for their reviewer
+    #[serde(default)]
+    pub review: bool,
+    /// States a file in the state can be marked as, any state if not given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next: Option<Vec<String>>,
+}
+
+impl State {
+    /// A state of the default workflow, moving to any state
+    fn new(name: &str, color: &str, todo: bool, review: bool) -> State {
+        State {
+            name: name.to_string(),
+            color: Some(color.to_string()),
+            letter: None,
+            todo,
+            review,
+            next: None,
+        }
+    }
+}
+
+/// Colour output mode
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorMode {
+    /// Colour output when writing to a terminal
+    Auto,
+    /// Always colour output
+    Always,
+    /// Never colour output
+    Never,
+}
+
+/// Cover strategy
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum CoverStrategy {
+    /// Copy only the files in the records
+    Records,
+    /// Copy every file in the translation directory, recorded or not
+    All,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            trans_dir: DEFAULT_TRANS_DIR.to_string(),
+            records_file: "records.toml".to_string(),
+            lock: false,
+            color: ColorMode::Auto,
+            include: Vec::new(),
+            progress: None,
+            workflow: vec![
+                State::new("Trans", "red", true, false),
+                State::new("Review", "yellow", true, true),
+                State::new("Done", "green", false, false),
+            ],
+            cover: CoverStrategy::Records,
+        }
+    }
+}
+
+impl Config {
+    /// Load configuration layers, later layers override earlier ones:
+    /// defaults, user config, repo config in the translation directory,
+    /// and `GIT_TRANS_*` environment variables
+    pub fn load(root_dir: &Path) -> Result<Config> {
+        let mut table = Table::new();
+        if let Some(user_config) = get_user_config() {
+            merge(&mut table, read_table(&user_config)?);
+        }
+        // the repo config lives in the translation directory named by the user and env layers
+        let env_table = read_env()?;
+        let mut trans_dir = DEFAULT_TRANS_DIR.to_string();
+        for layer in [&table, &env_table] {
+            if let Some(Value::String(dir)) = layer.get("trans_dir") {
+                trans_dir = dir.clone();
+            }
+        }
+        let repo_config = root_dir.join(trans_dir).join(CONFIG_FILE);
+        if repo_config.is_file() {
+            merge(&mut table, read_table(&repo_config)?);
+        }
+        merge(&mut table, env_table);
+
+        let config: Config = Value::Table(table)
+            .try_into()
+            .map_err(|err| GitTransError::Config(err.to_string()))?;
+        config.check_workflow()?;
+        Ok(config)
+    }
+
+    /// Check the workflow has states with unique names and letters, and the initial progress
+    /// and the transitions name its states
+    fn check_workflow(&self) -> Result<()> {
+        if self.workflow.is_empty() {
+            return Err(GitTransError::Config("workflow has no states".to_string()));
+        }
+        for (i, state) in self.workflow.iter().enumerate() {
+            if self.workflow[..i].iter().any(|s| s.name == state.name) {
+                return Err(GitTransError::Config(format!(
+                    "workflow state {} is defined twice",
+                    state.name
+                )));
+            }
+            if let Some(letter) = state.letter
+                && self.workflow[..i].iter().any(|s| s.letter == Some(letter))
+            {
+                return Err(GitTransError::Config(format!(
+                    "workflow letter {letter} is given twice"
+                )));
+            }
+            for next in state.next.iter().flatten() {
+                self.state(next)?;
+            }
+        }
+        if let Some(progress) = &self.progress {
+            self.state(&progress.0)?;
+        }
+        Ok(())
+    }
+
+    /// Find a workflow state by its name, ignoring case
+    pub fn state(&self, name: &str) -> Result<Progress> {
+        self.workflow
+            .iter()
+            .find(|state| state.name.eq_ignore_ascii_case(name))
+            .map(|state| Progress::new(&state.name))
+            .ok_or_else(|| GitTransError::UnknownState(name.to_string()))
+    }
+
+    /// Get the workflow state of a progress, `None` if the workflow has no such state
+    pub fn workflow_state(&self, progress: &Progress) -> Option<&State> {
+        self.workflow.iter().find(|state| state.name == progress.0)
+    }
+
+    /// Letter of a progress in status columns, the configured letter of its state or the
+    /// first letter of the name not taken by a configured letter or a state before it.
+    /// States unknown to the workflow get their first letter
+    pub fn letter(&self, progress: &Progress) -> char {
+        let first = |name: &str| name.chars().next().unwrap_or(' ').to_ascii_uppercase();
+        let mut taken: Vec<char> = self.workflow.iter().filter_map(|s| s.letter).collect();
+        for state in self.workflow.iter() {
+            let letter = state.letter.unwrap_or_else(|| {
+                state
+                    .name
+                    .chars()
+                    .filter(|c| c.is_alphanumeric())
+                    .map(|c| c.to_ascii_uppercase())
+                    .find(|c| !taken.contains(c))
+                    .unwrap_or_else(|| first(&state.name))
+            });
+            if state.name == progress.0 {
+                return letter;
+            }
+            taken.push(letter);
+        }
+        first(&progress.0)
+    }
+
+    /// Position of a progress in the workflow, `None` if the workflow has no such state
+    pub fn rank(&self, progress: &Progress) -> Option<usize> {
+        self.workflow
+            .iter()
+            .position(|state| state.name == progress.0)
+    }
+
+    /// Progress of files to translate, the first state of the workflow
+    pub fn first_progress(&self) -> Progress {
+        Progress::new(&self.workflow[0].name)
+    }
+
+    /// Progress of finished files, the last state of the workflow
+    pub fn last_progress(&self) -> Progress {
+        Progress::new(&self.workflow[self.workflow.len() - 1].name)
+    }
+
+    /// Progress of new files
+    pub fn initial_progress(&self) -> Progress {
+        self.progress
+            .clone()
+            .unwrap_or_else(|| self.first_progress())
+    }
+
+    /// Check if a file can be marked from a progress as another,
+    /// files in states unknown to the workflow can only move to the first state
+    pub fn can_move(&self, from: &Progress, to: &Progress) -> bool {
+        match self.workflow_state(from) {
+            Some(state) => match &state.next {
+                Some(next) => next.iter().any(|name| name.eq_ignore_ascii_case(&to.0)),
+                None => true,
+            },
+            None => to.0.eq_ignore_ascii_case(&self.first_progress().0),
+        }
+    }
+
+    /// Compile the default include patterns
+    pub fn include_patterns(&self) -> Result<Vec<glob::Pattern>> {
+        self.include
+            .iter()
+            .map(|pattern| {
+                glob::Pattern::new(pattern)
+                    .map_err(|err| GitTransError::Config(format!("include {pattern}: {err}")))
+            })
+            .collect()
+    }
+}
+
+/// Get the user config file, `$XDG_CONFIG_HOME/git-trans/config.toml`
+/// or `~/.config/git-trans/config.toml`
+fn get_user_config() -> Option<PathBuf> {
+    let config_dir = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| {
+            env::var_os("HOME")
+                .or_else(|| env::var_os("USERPROFILE"))
+                .map(|home| PathBuf::from(home).join(".config"))
+        })?;
+    let user_config = config_dir.join("git-trans").join(CONFIG_FILE);
+    user_config.is_file().then_some(user_config)
+}
+
+/// Read a configuration file into a toml table
+fn read_table(path: &Path) -> Result<Table> {
+    let content = fs::read_to_string(path)?;
+    toml::from_str(&content)
+        .map_err(|err| GitTransError::Config(format!("{}: {err}", path.display())))
+}
+
+/// Read `GIT_TRANS_*` environment variables into a toml table,
+/// e.g. `GIT_TRANS_LOCK=true` or `GIT_TRANS_INCLUDE=*.md,*.txt`
+fn read_env() -> Result<Table> {
+    let mut table = Table::new();
+    for (key, value) in env::vars() {
+        let Some(key) = key.strip_prefix(ENV_PREFIX) else {
+            continue;
+        };
+        let key = key.to_lowercase();
+        let value = match key.as_str() {
+            "lock" => Value::Boolean(
+                value
+                    .parse()
+                    .map_err(|_| GitTransError::Config(format!("{ENV_PREFIX}LOCK={value}")))?,
+            ),
+            "include" => Value::Array(
+                value
+                    .split(',')
+                    .filter(|p| !p.is_empty())
+                    .map(|p| Value::String(p.to_string()))
+                    .collect(),
+            ),
+            _ => Value::String(value),
+        };
+        table.insert(key, value);
+    }
+    Ok(table)
+}
+
+/// Merge a toml table into another, overriding existing keys
+fn merge(base: &mut Table, layer: Table) {
+    for (key, value) in layer {
+        base.insert(key, value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn workflow(toml: &str) -> Result<Config> {
+        let config: Config =
+            toml::from_str(toml).map_err(|err| GitTransError::Config(err.to_string()))?;
+        config.check_workflow()?;
+        Ok(config)
+    }
+
+    fn letters(config: &Config) -> String {
+        config
+            .workflow
+            .iter()
+            .map(|state| config.letter(&Progress::new(&state.name)))
+            .collect()
+    }
+
+    #[test]
+    fn default_letters() {
+        let config = Config::default();
+        assert_eq!(letters(&config), "TRD");
+        assert_eq!(config.letter(&Progress::new("legacy")), 'L');
+    }
+
+    #[test]
+    fn letters_avoid_taken_ones() {
+        let config = workflow(
+            r#"
+            [[workflow]]
+            name = "Proofread"
+            [[workflow]]
+            name = "Published"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(letters(&config), "PU");
+        // a configured letter is reserved before the states in front of it
+        let config = workflow(
+            r#"
+            [[workflow]]
+            name = "Trans"
+            [[workflow]]
+            name = "Final"
+            letter = "T"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(letters(&config), "RT");
+    }
+
+    #[test]
+    fn reject_duplicate_letters() {
+        let err = workflow(
+            r#"
+            [[workflow]]
+            name = "Trans"
+            letter = "X"
+            [[workflow]]
+            name = "Done"
+            letter = "X"
+            "#,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("letter X is given twice"));
+    }
+
+    #[test]
+    fn reject_unknown_next() {
+        let err = workflow(
+            r#"
+            [[workflow]]
+            name = "Trans"
+            next = ["Review"]
+            [[workflow]]
+            name = "Done"
+            "#,
+        )
+        .unwrap_err();
+        assert!(matches!(err, GitTransError::UnknownState(name) if name == "Review"));
+    }
+
+    #[test]
+    fn unknown_states_move_to_first() {
+        let config = Config::default();
+        let legacy = Progress::new("Legacy");
+        assert!(config.can_move(&legacy, &Progress::new("trans")));
+        assert!(!config.can_move(&legacy, &Progress::new("Done")));
+    }
+}
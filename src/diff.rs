@@ -0,0 +1,193 @@
+use std::path::Path;
+
+use crate::segment::{Kind, is_markdown, split_blocks};
+
+/// Options of upstream diffs
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DiffOptions {
+    /// Annotate each hunk with the corresponding lines of the translation
+    pub mapped: bool,
+    /// Diff by words instead of lines
+    pub word_diff: bool,
+    /// Render the old and new source in two columns
+    pub side_by_side: bool,
+    /// Join soft line breaks inside paragraphs before diffing
+    pub ignore_rewrap: bool,
+}
+
+impl DiffOptions {
+    /// Extra arguments of `git diff`
+    pub fn git_args(&self) -> Vec<&'static str> {
+        if self.word_diff {
+            vec!["--word-diff"]
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+/// Join the soft line breaks inside paragraphs, so that reflowed paragraphs are equal.
+/// Hard breaks, ending with two spaces or a backslash, and other Markdown blocks are kept
+pub fn unwrap_paragraphs(path: &Path, text: &str) -> String {
+    let mut unwrapped = String::new();
+    for block in split_blocks(text, is_markdown(path)) {
+        if block.kind != Kind::Paragraph {
+            unwrapped.push_str(block.raw);
+            continue;
+        }
+        let lead = block.raw.len() - block.raw.trim_start().len();
+        let mut joined = String::new();
+        for line in block.text().lines() {
+            joined.push_str(line.trim_start());
+            if line.ends_with("  ") || line.ends_with('\\') {
+                joined.push('\n');
+            } else {
+                joined.truncate(joined.trim_end().len());
+                joined.push(' ');
+            }
+        }
+        unwrapped.push_str(&block.raw[..lead]);
+        unwrapped.push_str(joined.trim_end_matches(' '));
+        unwrapped.push_str(&block.raw[lead + block.text().len()..]);
+    }
+    unwrapped
+}
+
+/// Width of the terminal from `COLUMNS`, 120 by default
+pub fn terminal_width() -> usize {
+    std::env::var("COLUMNS")
+        .ok()
+        .and_then(|columns| columns.parse().ok())
+        .unwrap_or(120)
+}
+
+/// Pad or truncate a line to a number of characters
+fn fit(line: &str, width: usize) -> String {
+    let line = line.replace('\t', "    ");
+    let mut fitted: String = line.chars().take(width).collect();
+    let len = fitted.chars().count();
+    fitted.extend(std::iter::repeat_n(' ', width - len));
+    fitted
+}
+
+/// Render a unified diff in two columns of the old and new lines. Changed lines are
+/// marked with `|`, removed ones with `<` and added ones with `>`, headers are kept
+pub fn side_by_side(diff: &str, width: usize) -> String {
+    let column = (width.saturating_sub(3) / 2).max(20);
+    let mut out = String::new();
+    let (mut removed, mut added): (Vec<&str>, Vec<&str>) = (Vec::new(), Vec::new());
+    let flush = |removed: &mut Vec<&str>, added: &mut Vec<&str>, out: &mut String| {
+        for k in 0..removed.len().max(added.len()) {
+            let (old, new) = (removed.get(k), added.get(k));
+            let marker = match (old, new) {
+                (Some(_), Some(_)) => '|',
+                (Some(_), None) => '<',
+                _ => '>',
+            };
+            let old = fit(old.copied().unwrap_or_default(), column);
+            let new = new.copied().unwrap_or_default().replace('\t', "    ");
+            out.push_str(format!("{old} {marker} {new}").trim_end());
+            out.push('\n');
+        }
+        removed.clear();
+        added.clear();
+    };
+    let mut in_hunk = false;
+    for line in diff.lines() {
+        match line.chars().next() {
+            Some('-') if in_hunk => removed.push(&line[1..]),
+            Some('+') if in_hunk => added.push(&line[1..]),
+            Some(' ') | None if in_hunk => {
+                flush(&mut removed, &mut added, &mut out);
+                let text = line.get(1..).unwrap_or_default();
+                out.push_str(format!("{}   {text}", fit(text, column)).trim_end());
+                out.push('\n');
+            }
+            _ => {
+                flush(&mut removed, &mut added, &mut out);
+                // `\ No newline at end of file` stays inside the hunk
+                in_hunk = line.starts_with("@@") || in_hunk && line.starts_with('\\');
+                out.push_str(line);
+                out.push('\n');
+            }
+        }
+    }
+    flush(&mut removed, &mut added, &mut out);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unwrap_hard_breaks() {
+        let text = "one\ntwo  \nthree\\\nfour\nfive\n";
+        assert_eq!(
+            unwrap_paragraphs(Path::new("a.md"), text),
+            "one two  \nthree\\\nfour five\n"
+        );
+    }
+
+    #[test]
+    fn unwrap_indented_paragraph() {
+        let text = "Intro.\n\n  indented\n    and wrapped\n";
+        assert_eq!(
+            unwrap_paragraphs(Path::new("a.md"), text),
+            "Intro.\n\n  indented and wrapped\n"
+        );
+    }
+
+    #[test]
+    fn keep_other_blocks() {
+        let text =
+            "# Title\nline\n\n- item\n  wrapped\n\n```\ncode\nlines\n```\n\n> quote\n> lines\n";
+        assert_eq!(
+            unwrap_paragraphs(Path::new("a.md"), text),
+            "# Title\nline\n\n- item\n  wrapped\n\n```\ncode\nlines\n```\n\n> quote\n> lines\n"
+        );
+        // other files are split into paragraphs only
+        assert_eq!(
+            unwrap_paragraphs(Path::new("a.txt"), "# not\na heading\n"),
+            "# not a heading\n"
+        );
+    }
+
+    #[test]
+    fn side_by_side_unequal_runs() {
+        let diff = "--- a/a.md\n+++ b/a.md\n@@ -1,3 +1,2 @@\n same\n-old one\n-old two\n+new\n";
+        assert_eq!(
+            side_by_side(diff, 43),
+            "--- a/a.md\n+++ b/a.md\n@@ -1,3 +1,2 @@\n\
+             same                   same\n\
+             old one              | new\n\
+             old two              <\n"
+        );
+        let diff = "@@ -1 +1,2 @@\n-old\n+new\n+added\n";
+        assert_eq!(
+            side_by_side(diff, 43),
+            "@@ -1 +1,2 @@\nold                  | new\n                     > added\n"
+        );
+    }
+
+    #[test]
+    fn side_by_side_no_newline() {
+        let diff =
+            "@@ -1 +1 @@\n-old\n\\ No newline at end of file\n+new\n\\ No newline at end of file\n";
+        assert_eq!(
+            side_by_side(diff, 43),
+            "@@ -1 +1 @@\nold                  <\n\\ No newline at end of file\n\
+             \x20                    > new\n\\ No newline at end of file\n"
+        );
+    }
+
+    #[test]
+    fn side_by_side_narrow() {
+        // the columns are at least 20 characters wide, longer lines are truncated on the left
+        let diff = "@@ -1 +1 @@\n-a line longer than twenty\n+short\n";
+        assert_eq!(
+            side_by_side(diff, 10),
+            "@@ -1 +1 @@\na line longer than t | short\n"
+        );
+    }
+}
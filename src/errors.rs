@@ -0,0 +1,130 @@
+use std::path::PathBuf;
+use thiserror::Error;
+
+/// Errors of git-trans, each kind exits the process with a stable code
+///
+/// | Code | Error                                |
+/// |------|--------------------------------------|
+/// | 1    | other errors                         |
+/// | 2    | invalid arguments, reported by clap  |
+/// | 3    | not a git repository                 |
+/// | 4    | not initialized                      |
+/// | 5    | record not found                     |
+/// | 6    | git command failed                   |
+/// | 7    | records parse error                  |
+/// | 8    | path outside repo                    |
+/// | 9    | uncommitted changes in the worktree  |
+/// | 10   | not a linked worktree                |
+/// | 130  | interrupted before the build command |
+///
+/// A failed `build` command exits with the exit code of the command it ran.
+#[derive(Debug, Error)]
+pub enum GitTransError {
+    /// Current directory is not in a git repository
+    #[error("not a git repository")]
+    NotARepo,
+    /// The repository has no records file
+    #[error("this repo is not initialized with git-trans")]
+    NotInitialized,
+    /// The path is not in the records
+    #[error("record not found: {}", .0.display())]
+    RecordNotFound(PathBuf),
+    /// The path is already in the records
+    #[error("record already exists: {}", .0.display())]
+    RecordExists(PathBuf),
+    /// The file is not tracked by git
+    #[error("file is not tracked by git: {}", .0.display())]
+    NotTracked(PathBuf),
+    /// The source file is deleted or renamed upstream
+    #[error("source file is deleted or renamed upstream: {}", .0.display())]
+    Orphaned(PathBuf),
+    /// The revision does not exist
+    #[error("{0} is not a valid revision")]
+    InvalidRevision(String),
+    /// A git command failed to run or exited with an error
+    #[error("failed to execute: git {command}: {message}")]
+    GitCommandFailed { command: String, message: String },
+    /// The records file is corrupted
+    #[error("failed to parse {}: {source}", .path.display())]
+    RecordsParse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    /// The path is outside of the repository
+    #[error("path is outside of the repository: {}", .0.display())]
+    PathOutsideRepo(PathBuf),
+    /// The working tree has uncommitted changes cover would mix with the translations
+    #[error(
+        "uncommitted changes, commit or stash them first: {}",
+        .0.iter().map(|p| p.display().to_string()).collect::<Vec<_>>().join(", ")
+    )]
+    DirtyTree(Vec<PathBuf>),
+    /// The directory exists but is not a linked worktree of the repository
+    #[error("not a linked worktree of this repository: {}", .0.display())]
+    NotAWorktree(PathBuf),
+    /// The configuration is invalid
+    #[error("invalid configuration: {0}")]
+    Config(String),
+    /// The command run by `build` failed
+    #[error("`{command}` {}", match .code {
+        Some(code) => format!("exited with code {code}"),
+        None => "was terminated by a signal".to_string(),
+    })]
+    BuildFailed { command: String, code: Option<i32> },
+    /// `build` was interrupted before running its command
+    #[error("interrupted before running `{0}`")]
+    Interrupted(String),
+    /// The language is not in the records
+    #[error("language {0} is not initialized, run `git trans init {0}` first")]
+    UnknownLang(String),
+    /// The language is already in the records
+    #[error("language {0} is already initialized")]
+    LangExists(String),
+    /// A revision is given when adding a language, whose files track their own revisions
+    #[error("cannot track {0} when adding a language, it only applies to a new project")]
+    LangTag(String),
+    /// The state is not in the configured workflow
+    #[error("unknown workflow state: {0}")]
+    UnknownState(String),
+    /// The workflow does not allow marking the file as the state
+    #[error("cannot mark {} as {to}, it is {from}", .path.display())]
+    InvalidTransition {
+        path: PathBuf,
+        from: String,
+        to: String,
+    },
+    /// The staged changes break the rules of a translation commit
+    #[error("commit rejected, {0} problems found")]
+    CommitRejected(usize),
+    /// Some of the files in a batch failed
+    #[error("{failed} of {total} files failed")]
+    Partial { failed: usize, total: usize },
+    /// I/O error
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+impl GitTransError {
+    /// Process exit code of the error
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            GitTransError::NotARepo => 3,
+            GitTransError::NotInitialized | GitTransError::UnknownLang(_) => 4,
+            GitTransError::RecordNotFound(_) => 5,
+            GitTransError::GitCommandFailed { .. } => 6,
+            GitTransError::RecordsParse { .. } => 7,
+            GitTransError::PathOutsideRepo(_) => 8,
+            GitTransError::DirtyTree(_) => 9,
+            GitTransError::NotAWorktree(_) => 10,
+            GitTransError::Interrupted(_) => 130,
+            GitTransError::BuildFailed {
+                code: Some(code @ 1..=255),
+                ..
+            } => *code as u8,
+            _ => 1,
+        }
+    }
+}
+
+/// Result with git-trans errors
+pub type Result<T, E = GitTransError> = std::result::Result<T, E>;
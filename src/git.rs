@@ -1,114 +1,387 @@
-use std::{error::Error, fs, path::Path, path::PathBuf, process::Command};
+use std::{
+    fs,
+    path::Path,
+    path::PathBuf,
+    process::{Command, Output},
+};
 
-/// Get the root directory of the git repository
-pub fn get_root_dir() -> Option<PathBuf> {
-    let root_dir = Command::new("git")
-        .args(["rev-parse", "--show-toplevel"])
-        .output()
-        .expect("failed to execute: git rev-parse --show-toplevel");
+use crate::errors::{GitTransError, Result};
 
-    if root_dir.status.code().unwrap() != 0 {
-        eprintln!("not a git repository");
-        return None;
+/// Run a git command and return its output,
+/// fails if git cannot be executed or exits with an error
+fn run_git(cmd: &mut Command) -> Result<Output> {
+    let command = cmd
+        .get_args()
+        .map(|arg| arg.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join(" ");
+    let output = cmd
+        .output()
+        .map_err(|err| GitTransError::GitCommandFailed {
+            command: command.clone(),
+            message: err.to_string(),
+        })?;
+    if !output.status.success() {
+        return Err(GitTransError::GitCommandFailed {
+            command,
+            message: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        });
     }
+    Ok(output)
+}
+
+/// A git command run in the root directory, so that paths relative to the root
+/// directory can be passed to it
+fn git(root: &Path) -> Command {
+    let mut cmd = Command::new("git");
+    cmd.current_dir(root);
+    cmd
+}
+
+/// Get the root directory of the git repository containing a directory
+pub fn get_root_dir(dir: &Path) -> Option<PathBuf> {
+    let root_dir = run_git(git(dir).args(["rev-parse", "--show-toplevel"])).ok()?;
 
     let root_dir = String::from_utf8_lossy(&root_dir.stdout).trim().to_string();
-    let absolute_path = fs::canonicalize(root_dir)
-        .unwrap()
-        .to_str()
-        .unwrap()
-        .to_string();
+    let absolute_path = fs::canonicalize(root_dir).ok()?.to_str()?.to_string();
     if cfg!(windows) {
         return Some(PathBuf::from(absolute_path.replace("/", "\\")));
     }
     Some(PathBuf::from(absolute_path))
 }
 
-/// Get current directory path prefix
-#[allow(dead_code)]
-pub fn get_prefix() -> Option<PathBuf> {
-    let prefix = Command::new("git")
-        .args(["rev-parse", "--show-prefix"])
-        .output()
-        .expect("failed to execute: git rev-parse --show-prefix");
+/// List git tracked files matching a pathspec relative to the root directory or absolute,
+/// ignored files are skipped. The returned paths are relative to the root directory
+pub fn list_tracked_files(root: &Path, pathspec: &Path) -> Result<Vec<PathBuf>> {
+    let ls_files = |ignored: bool| -> Result<Vec<PathBuf>> {
+        let mut cmd = git(root);
+        cmd.args(["ls-files", "--full-name", "-z"]);
+        if ignored {
+            cmd.args(["--cached", "--ignored", "--exclude-standard"]);
+        }
+        let files = run_git(cmd.arg("--").arg(pathspec))?;
+        Ok(String::from_utf8_lossy(&files.stdout)
+            .split('\0')
+            .filter(|file| !file.is_empty())
+            .map(PathBuf::from)
+            .collect())
+    };
+    let ignored = ls_files(true)?;
+    Ok(ls_files(false)?
+        .into_iter()
+        .filter(|file| !ignored.contains(file))
+        .collect())
+}
 
-    if prefix.status.code().unwrap() != 0 {
-        eprintln!("not a git repository");
-        return None;
-    }
+/// Get the revision from a tag
+pub fn get_tag_rev(root: &Path, tag: &str) -> Option<String> {
+    let revision = run_git(git(root).args(["rev-parse", tag])).ok()?;
 
-    let prefix = String::from_utf8_lossy(&prefix.stdout).trim().to_string();
+    let revision = String::from_utf8_lossy(&revision.stdout).trim().to_string();
+    Some(revision)
+}
 
-    if cfg!(windows) {
-        return Some(PathBuf::from(prefix.replace("/", "\\")));
-    }
-    Some(PathBuf::from(prefix))
+/// Get the current revision of a file, empty if the file has no history
+pub fn get_file_rev(root: &Path, path: &Path) -> Result<String> {
+    let file_revision = run_git(
+        git(root)
+            .args(["log", "-n", "1", "--pretty=format:%H", "--"])
+            .arg(path),
+    )?;
+    Ok(String::from_utf8_lossy(&file_revision.stdout)
+        .trim()
+        .to_string())
 }
 
-/// Get the revision from a tag
-pub fn get_tag_rev(tag: &String) -> Option<String> {
-    let revision = Command::new("git")
-        .args(["rev-parse", tag])
-        .output()
-        .unwrap_or_else(|_| panic!("failed to execute: git rev-parse {}", tag));
+/// Get the commit date and subject of a revision
+pub fn get_rev_info(root: &Path, rev: &str) -> Option<(String, String)> {
+    let info =
+        run_git(git(root).args(["log", "-n", "1", "--pretty=format:%ai%x09%s", rev, "--"])).ok()?;
+    let info = String::from_utf8_lossy(&info.stdout).trim().to_string();
+    info.split_once('\t')
+        .map(|(date, subject)| (date.to_string(), subject.to_string()))
+}
+
+/// Count the commits that changed a file since a revision
+pub fn count_commits_since(root: &Path, path: &Path, rev: &str) -> Result<usize> {
+    let count = run_git(
+        git(root)
+            .args(["rev-list", "--count", &format!("{rev}..HEAD"), "--"])
+            .arg(path),
+    )?;
+    Ok(String::from_utf8_lossy(&count.stdout)
+        .trim()
+        .parse()
+        .unwrap_or(0))
+}
 
-    if revision.status.code().unwrap() != 0 {
-        eprintln!("not a git revision");
-        return None;
+/// Count the lines added and deleted in a file since a revision, binary changes count as none
+pub fn count_changed_lines_since(root: &Path, path: &Path, rev: &str) -> Result<usize> {
+    let numstat = run_git(
+        git(root)
+            .args(["diff", "--numstat", rev, "HEAD", "--"])
+            .arg(path),
+    )?;
+    Ok(String::from_utf8_lossy(&numstat.stdout)
+        .lines()
+        .flat_map(|line| line.split('\t').take(2))
+        .filter_map(|count| count.parse::<usize>().ok())
+        .sum())
+}
+
+/// Get the content of a file at a revision, the path is relative to the root directory
+pub fn get_file_at_rev(root: &Path, path: &Path, rev: &str) -> Option<Vec<u8>> {
+    let content = run_git(
+        git(root)
+            .arg("show")
+            .arg(format!("{rev}:{}", path.display())),
+    )
+    .ok()?;
+    Some(content.stdout)
+}
+
+/// Get the blob hash of a file at a revision, the path is relative to the root directory
+pub fn get_blob(root: &Path, path: &Path, rev: &str) -> Option<String> {
+    let blob = run_git(
+        git(root)
+            .arg("rev-parse")
+            .arg(format!("{rev}:{}", path.display())),
+    )
+    .ok()?;
+    Some(String::from_utf8_lossy(&blob.stdout).trim().to_string())
+}
+
+/// Find the new path of a file renamed since a revision, with rename detection between
+/// the revision and HEAD. The paths are relative to the root directory, `None` if the
+/// file is deleted or the revision cannot be compared
+pub fn get_renamed_path(root: &Path, path: &Path, rev: &str, trans_dir: &str) -> Option<PathBuf> {
+    // the copies in the translation directory are never rename targets
+    let exclude = format!(":(exclude){trans_dir}");
+    let diff = run_git(git(root).args([
+        "diff",
+        "-M",
+        "-z",
+        "--name-status",
+        "--diff-filter=R",
+        rev,
+        "HEAD",
+        "--",
+        ".",
+        &exclude,
+    ]))
+    .ok()?;
+    let diff = String::from_utf8_lossy(&diff.stdout);
+    // renames are listed as `R<score>\0<old path>\0<new path>\0`
+    let mut fields = diff.split('\0');
+    while let (Some(_status), Some(from), Some(to)) = (fields.next(), fields.next(), fields.next())
+    {
+        if Path::new(from) == path && !Path::new(to).starts_with(trans_dir) {
+            return Some(PathBuf::from(to));
+        }
     }
+    None
+}
 
-    let revision = String::from_utf8_lossy(&revision.stdout).trim().to_string();
-    Some(revision)
+/// Check if an object exists in the repository
+pub fn object_exists(root: &Path, hash: &str) -> bool {
+    run_git(git(root).args(["cat-file", "-e", hash])).is_ok()
 }
 
-/// Get the current revision of a file
-pub fn get_file_rev(path: &Path) -> String {
-    let file_revision = Command::new("git")
-        .args(["log", "-n", "1", "--pretty=format:%H", "--"])
-        .arg(path)
-        .output()
-        .expect("failed to execute: git log -n 1 --pretty=format:%H -- <path>");
-    String::from_utf8_lossy(&file_revision.stdout)
-        .trim()
-        .to_string()
+/// Get diff between two contents of a file with extra arguments of `git diff`, the contents
+/// are written to temporary files instead of the object database and named by the path
+pub fn get_content_diff(path: &Path, old: &[u8], new: &[u8], args: &[&str]) -> Result<String> {
+    let dir = std::env::temp_dir().join(format!("git-trans-{}", std::process::id()));
+    fs::create_dir_all(&dir)?;
+    let diff = fs::write(dir.join("old"), old)
+        .and_then(|_| fs::write(dir.join("new"), new))
+        .map_err(GitTransError::from)
+        .and_then(|_| {
+            let command = format!("diff --no-index {}", args.join(" "));
+            let output = git(&dir)
+                .args(["diff", "--no-index"])
+                .args(args)
+                .args(["--", "old", "new"])
+                .output()
+                .map_err(|err| GitTransError::GitCommandFailed {
+                    command: command.clone(),
+                    message: err.to_string(),
+                })?;
+            // `--no-index` exits with 1 when the contents differ
+            match output.status.code() {
+                Some(0 | 1) => Ok(output.stdout),
+                _ => Err(GitTransError::GitCommandFailed {
+                    command,
+                    message: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+                }),
+            }
+        });
+    let _ = fs::remove_dir_all(&dir);
+    let path = path.display();
+    let diff = String::from_utf8_lossy(&diff?).to_string();
+    // only the headers before the first hunk are renamed, not the changed lines
+    let header = diff.find("\n@@").map_or(diff.len(), |pos| pos + 1);
+    Ok(diff[..header]
+        .replace("a/old", &format!("a/{path}"))
+        .replace("b/new", &format!("b/{path}"))
+        + &diff[header..])
 }
 
-/// Get diff between two revisions of a file
-pub fn get_diff(path: &Path, old_rev: &str, new_rev: &str) -> String {
-    let diff = Command::new("git")
-        .args(["diff", old_rev, new_rev, path.to_str().unwrap()])
-        .output()
-        .expect("failed to execute: git diff {old_rev} {new_rev} {path}");
-    String::from_utf8_lossy(&diff.stdout).to_string()
+/// Get diff between two blobs of a file with extra arguments of `git diff`,
+/// the blob names in the headers are replaced by the path
+pub fn get_blob_diff(
+    root: &Path,
+    path: &Path,
+    old_blob: &str,
+    new_blob: &str,
+    args: &[&str],
+) -> Result<String> {
+    let diff = run_git(git(root).arg("diff").args(args).args([old_blob, new_blob]))?;
+    let path = path.display();
+    Ok(String::from_utf8_lossy(&diff.stdout)
+        .replace(&format!("a/{old_blob}"), &format!("a/{path}"))
+        .replace(&format!("b/{new_blob}"), &format!("b/{path}")))
+}
+
+/// Get diff between two revisions of a file with extra arguments of `git diff`
+pub fn get_diff(
+    root: &Path,
+    path: &Path,
+    old_rev: &str,
+    new_rev: &str,
+    args: &[&str],
+) -> Result<String> {
+    let diff = run_git(
+        git(root)
+            .arg("diff")
+            .args(args)
+            .args([old_rev, new_rev, "--"])
+            .arg(path),
+    )?;
+    Ok(String::from_utf8_lossy(&diff.stdout).to_string())
 }
 
 /// Show logs in the .trans folder
-pub fn get_log(path: &Path) -> String {
-    let log = Command::new("git")
-        .args(["log", "--", path.to_str().unwrap()])
-        .output()
-        .expect("failed to execute: git log -- {path}");
-    String::from_utf8_lossy(&log.stdout).to_string()
-}
-
-/// Reset the root folder to the latest revision
-pub fn reset() -> Result<(), Box<dyn Error>> {
-    let output = Command::new("git")
-        .args([
-            "restore",
-            "--source=HEAD",
-            "--staged",
-            "--worktree",
-            ".",
-            ":(exclude).trans/",
-        ])
-        .output()?;
-
-    if output.status.success() {
-        Ok(())
+pub fn get_log(root: &Path, path: &Path) -> Result<String> {
+    let log = run_git(git(root).args(["log", "--"]).arg(path))?;
+    Ok(String::from_utf8_lossy(&log.stdout).to_string())
+}
+
+/// Get the email of the git user, `None` if `user.email` is not set
+pub fn get_user_email(root: &Path) -> Option<String> {
+    let email = run_git(git(root).args(["config", "user.email"])).ok()?;
+    let email = String::from_utf8_lossy(&email.stdout).trim().to_string();
+    (!email.is_empty()).then_some(email)
+}
+
+/// Get the path of a file in the git directory, like `.git/<name>`
+pub fn get_git_path(root: &Path, name: &str) -> Result<PathBuf> {
+    let git_path = run_git(git(root).args(["rev-parse", "--git-path", name]))?;
+    Ok(root.join(String::from_utf8_lossy(&git_path.stdout).trim()))
+}
+
+/// List tracked files with uncommitted changes, staged or not, relative to the root directory
+pub fn list_modified_files(root: &Path) -> Result<Vec<PathBuf>> {
+    let status = run_git(git(root).args(["status", "--porcelain", "-z", "--untracked-files=no"]))?;
+    let status = String::from_utf8_lossy(&status.stdout);
+    // entries are `XY <path>`, renames and copies are followed by their original path
+    let mut entries = status.split('\0').filter(|entry| !entry.is_empty());
+    let mut modified = Vec::new();
+    while let Some(entry) = entries.next() {
+        let (code, path) = entry.split_at(3.min(entry.len()));
+        if code.contains(['R', 'C']) {
+            entries.next();
+        }
+        modified.push(PathBuf::from(path));
+    }
+    Ok(modified)
+}
+
+/// List the linked worktrees of a repository, the main worktree is left out
+pub fn list_linked_worktrees(root: &Path) -> Result<Vec<PathBuf>> {
+    let list = run_git(git(root).args(["worktree", "list", "--porcelain", "-z"]))?;
+    // the main worktree is listed first, each worktree starts with `worktree <path>`
+    Ok(String::from_utf8_lossy(&list.stdout)
+        .split('\0')
+        .filter_map(|line| line.strip_prefix("worktree "))
+        .skip(1)
+        .map(PathBuf::from)
+        .collect())
+}
+
+/// Check if a directory is a linked worktree of a repository, never its main worktree
+pub fn is_linked_worktree(root: &Path, dir: &Path) -> Result<bool> {
+    let Ok(dir) = fs::canonicalize(dir) else {
+        return Ok(false);
+    };
+    Ok(list_linked_worktrees(root)?
+        .iter()
+        .any(|worktree| fs::canonicalize(worktree).is_ok_and(|worktree| worktree == dir)))
+}
+
+/// Check out a revision in a detached worktree. An existing directory is only reused if it is
+/// a linked worktree of the repository, it is switched to the revision keeping its local changes
+pub fn add_worktree(root: &Path, path: &Path, rev: &str) -> Result<()> {
+    if path.exists() {
+        if !is_linked_worktree(root, path)? {
+            return Err(GitTransError::NotAWorktree(path.to_path_buf()));
+        }
+        run_git(git(path).args(["checkout", "--detach", rev]))?;
     } else {
-        Err("failed to execute: git reset --hard HEAD -- . :!.trans/"
-            .to_string()
-            .into())
+        run_git(
+            git(root)
+                .args(["worktree", "add", "--detach"])
+                .arg(path)
+                .arg(rev),
+        )?;
+    }
+    Ok(())
+}
+
+/// Export the files of a revision into a directory, without touching the index
+pub fn export_tree(root: &Path, dir: &Path, rev: &str) -> Result<()> {
+    // a temporary index keeps the index of the working tree untouched
+    let index = get_git_path(root, "git-trans-export-index")?;
+    fs::create_dir_all(dir)?;
+    let prefix = format!("{}/", dir.display());
+    let exported = run_git(
+        git(root)
+            .env("GIT_INDEX_FILE", &index)
+            .args(["read-tree", rev]),
+    )
+    .and_then(|_| {
+        run_git(
+            git(root)
+                .env("GIT_INDEX_FILE", &index)
+                .args(["checkout-index", "--all", "--force"])
+                .arg(format!("--prefix={prefix}")),
+        )
+    });
+    if index.is_file() {
+        fs::remove_file(index)?;
+    }
+    exported.map(|_| ())
+}
+
+/// List the files staged for the next commit, relative to the root directory
+pub fn list_staged_files(root: &Path) -> Result<Vec<PathBuf>> {
+    let staged =
+        run_git(git(root).args(["diff", "--cached", "--name-only", "--no-renames", "-z"]))?;
+    Ok(String::from_utf8_lossy(&staged.stdout)
+        .split('\0')
+        .filter(|file| !file.is_empty())
+        .map(PathBuf::from)
+        .collect())
+}
+
+/// Reset files to the latest revision, the paths are relative to the root directory
+pub fn reset(root: &Path, paths: &[PathBuf]) -> Result<()> {
+    if paths.is_empty() {
+        return Ok(());
     }
+    run_git(
+        git(root)
+            .args(["restore", "--source=HEAD", "--staged", "--worktree", "--"])
+            .args(paths),
+    )?;
+    Ok(())
 }
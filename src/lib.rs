@@ -0,0 +1,33 @@
+//! A translation toolbox for projects using git.
+//!
+//! Open the project of the current directory with [`Project::open`], or of
+//! any directory with [`Project::open_at`], then operate on its records:
+//!
+//! ```no_run
+//! use git_trans::{Progress, Project};
+//! use std::path::PathBuf;
+//!
+//! let mut project = Project::open()?;
+//! let batch = project.mark(Progress::new("Review"), &[PathBuf::from("doc/")], None)?;
+//! for file in batch.done {
+//!     println!("{}", file.path.display());
+//! }
+//! # Ok::<(), git_trans::GitTransError>(())
+//! ```
+
+pub mod config;
+pub mod diff;
+pub mod errors;
+pub mod git;
+pub mod merge;
+pub mod output;
+pub mod project;
+pub mod records;
+pub mod segment;
+pub mod stats;
+pub mod utils;
+
+pub use errors::{GitTransError, Result};
+pub use project::{Batch, CoverOptions, CoverTarget, Project, Repo};
+pub use records::{HistoryEntry, Progress, Records, Summary, TrackedFile, UpstreamChange};
+pub use stats::{Group, Stats, Tally};
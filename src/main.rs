@@ -1,189 +1,575 @@
 use Commands::*;
 use clap::Parser;
-#[allow(unused)]
-use clap::error::ErrorKind as ClapErrorKind;
-use core::todo;
-use log::{debug, error, info};
-use std::fs;
-use std::io::{Error, ErrorKind, Write};
-use std::path::{Path, PathBuf};
+use log::info;
+use std::path::Path;
+use std::process::ExitCode;
 
 mod cmd;
-mod git;
-mod records;
-mod utils;
+mod show;
 
 use cmd::*;
-use git::*;
-use records::Progress;
-use records::*;
-use utils::*;
+use git_trans::config::Config;
+use git_trans::diff::DiffOptions;
+use git_trans::git::get_user_email;
+use git_trans::output::{
+    FileRow, Format, LangRow, StatRow, TodoRow, render, render_one, stat_rows,
+};
+use git_trans::utils::get_dir_rel_to_root;
+use git_trans::{
+    Batch, CoverOptions, CoverTarget, GitTransError, Progress, Project, Records, Repo, Result,
+    TrackedFile, UpstreamChange,
+};
+use show::*;
 
-#[allow(unused)]
-pub fn main() -> Result<(), Error> {
+pub fn main() -> ExitCode {
     // initial logger and cli parser
     env_logger::init();
     let cli = Cli::parse();
 
-    // check if this is a git repo
-    if get_root_dir().is_none() {
-        let not_a_repo = Error::new(ErrorKind::NotFound, "This is not a repo");
-        return Err(not_a_repo);
+    match run(&cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {err}");
+            ExitCode::from(err.exit_code())
+        }
+    }
+}
+
+fn run(cli: &Cli) -> Result<()> {
+    // init .trans folder
+    if let Init { lang, tag } = &cli.command {
+        let project = Project::init(lang, tag)?;
+        info!("{} initialized.", project.repo.trans_dir().display());
+        return Ok(());
+    }
+
+    // check the staged changes, even if the records in the working tree are broken
+    if let CheckCommit = &cli.command {
+        let problems = Project::check_commit()?;
+        for problem in problems.iter() {
+            eprintln!("{problem}");
+        }
+        if !problems.is_empty() {
+            return Err(GitTransError::CommitRejected(problems.len()));
+        }
+        return Ok(());
+    }
+
+    let mut project = Project::open_lang(cli.lang.as_deref())?;
+    let repo = project.repo.clone();
+    let config = &repo.config;
+    apply_color(config);
+    let format = cli.format;
+    if format != Format::Text {
+        colored::control::set_override(false);
     }
 
-    // get repo dir
-    let root_dir: PathBuf = get_root_dir().unwrap();
-    // get .trans dir
-    let trans_dir = get_trans_dir();
-    // get records.toml file
-    let records_toml = get_records_toml();
+    // states are checked once the workflow is loaded
+    if let Show {
+        status: ShowStatus::State(name),
+        ..
+    } = &cli.command
+    {
+        config.state(name)?;
+    }
 
     match &cli.command {
-        // init .trans folder
-        Init { lang, tag } => {
-            let content = toml::to_string(&Records::init(lang, tag).unwrap()).unwrap();
-            create_file_with_dirs(records_toml)
-                .map(|mut file| {
-                    info!("File .trans/records.toml created.");
-                    file.write_all(content.as_bytes());
-                })
-                .inspect_err(|err| {
-                    error!("File .trans/records.toml created failed.");
-                })
-        }
-
-        _ => {
-            // check if records.toml exists
-            if !Path::new(&records_toml).is_file() {
-                eprintln!("this repo is not initialized with git-trans");
-                let not_exsist = Error::new(
-                    ErrorKind::NotFound,
-                    "this repo is not initialized with git-trans",
-                );
-                return Err(not_exsist);
-            }
-
-            let mut records_str = fs::read_to_string(&records_toml)?;
-            let mut records: Records = toml::from_str(&records_str).unwrap();
-
-            match &cli.command {
-                Add {
-                    path_args: path,
-                    lock,
-                } => {
-                    let path = path[0].to_path_buf();
-                    let path_rel_to_root = get_path_rel_to_root(&path);
-
-                    copy_file_to_trans(&path)?;
-
-                    let added_file = records.add(&path, *lock)?;
-                    records_str = toml::to_string(&records).unwrap();
-                    fs::write(&records_toml, records_str)?;
-                    Ok(())
-                }
-                Rm { path_args: path } => {
-                    let removed_file = records.remove(&path[0].to_path_buf()).unwrap();
-                    records_str = toml::to_string(&records).unwrap();
-                    fs::write(&records_toml, records_str).unwrap();
-                    Ok(())
-                }
-                Ls {
-                    path,
-                    all,
-                    recursive,
-                } => {
-                    debug!(
-                        "'git trans ls' was run,\npath: {}\nall: {}",
-                        path.as_ref().unwrap().to_path_buf().display(),
-                        all
-                    );
-                    Ok(())
-                }
-                Diff {
-                    path_args: path,
-                    gendiff,
-                } => {
-                    let path = &path[0].to_path_buf();
-                    let old_rev = records.get(path).unwrap().track_rev;
-                    let new_rev = get_file_rev(path);
-                    let diff_file = get_diff(path, &old_rev, &new_rev);
-                    if *gendiff {
-                        write_diff_file_to_trans(path, &diff_file)?;
-                    } else {
-                        println!("{}", diff_file);
-                    }
-                    Ok(())
+        Add {
+            path_args: path,
+            lock,
+            include,
+            exclude,
+        } => report("add", project.add(path, *lock, include, exclude)?),
+        Rm { path_args: path } => report("remove", project.remove(path)?),
+        Ls {
+            path,
+            all,
+            recursive,
+        } => {
+            let path = path.as_deref().unwrap_or(Path::new("./"));
+            let dir = get_dir_rel_to_root(&repo.root_dir, path)?;
+            match (format, *all) {
+                (Format::Text, true) => show_all(config, &project.records),
+                (Format::Text, false) => show_dir(config, &project.records, &dir, *recursive),
+                (_, true) => print_files(project.records.files.iter().collect(), format),
+                (_, false) => print_files(project.records.in_dir(&dir, *recursive).0, format),
+            }
+            Ok(())
+        }
+        Info { path } => {
+            let file = project.records.get(&repo, path)?;
+            match format {
+                Format::Text => show_info(&repo, &file)?,
+                _ => println!("{}", render_one(&file.info(&repo)?, format)),
+            }
+            Ok(())
+        }
+        History { path } => {
+            let file = project.records.get(&repo, path)?;
+            match format {
+                Format::Text => show_history(&file),
+                _ => println!(
+                    "{}",
+                    render(file.history.as_deref().unwrap_or_default(), format)
+                ),
+            }
+            Ok(())
+        }
+        Stats { depth, all_langs } => {
+            if !*all_langs {
+                let stats = project.records.stats(&repo, *depth)?;
+                match format {
+                    Format::Text => print!("{stats}"),
+                    _ => println!("{}", render(&stat_rows(&stats), format)),
                 }
-                Cover => {
-                    let count = cover()?;
-                    Ok(())
+                return Ok(());
+            }
+            let stats: Vec<_> = project
+                .records
+                .each_lang()
+                .iter()
+                .map(|records| records.stats(&repo.for_records(records), *depth))
+                .collect::<Result<_>>()?;
+            if format != Format::Text {
+                let rows: Vec<LangRow<StatRow>> = stats
+                    .iter()
+                    .flat_map(|stats| {
+                        stat_rows(stats).into_iter().map(|row| LangRow {
+                            row,
+                            lang: &stats.lang,
+                        })
+                    })
+                    .collect();
+                println!("{}", render(&rows, format));
+                return Ok(());
+            }
+            for stats in stats.iter() {
+                println!("{stats}");
+            }
+            Ok(())
+        }
+        Diff {
+            path_args: path,
+            gendiff,
+            mapped,
+            word_diff,
+            side_by_side,
+            ignore_rewrap,
+            all_unsynced,
+        } => {
+            let path = &match all_unsynced {
+                true => project.unsynced_paths(),
+                false => path.clone(),
+            };
+            if path.is_empty() {
+                println!("No files are Unsynced.");
+                return Ok(());
+            }
+            let options = DiffOptions {
+                mapped: *mapped,
+                word_diff: *word_diff,
+                side_by_side: *side_by_side,
+                ignore_rewrap: *ignore_rewrap,
+            };
+            if *gendiff {
+                return report("diff", project.gendiff(path, &options)?);
+            }
+            // only the patches go to stdout, so they can be redirected to a file
+            report_with(
+                "diff",
+                project.diff(path, &options)?,
+                |(file, diff_file)| {
+                    println!("{}", diff_file);
+                    print_outdated(&repo, file);
+                },
+            )
+        }
+        Cover {
+            min_progress,
+            dry_run,
+            worktree,
+            out,
+            rev,
+        } => {
+            let target = match (worktree, out) {
+                (Some(dir), _) => CoverTarget::Worktree(dir.clone()),
+                (_, Some(dir)) => CoverTarget::Out(dir.clone()),
+                _ => CoverTarget::Root,
+            };
+            let options = CoverOptions {
+                min_progress: min_progress
+                    .as_deref()
+                    .map(|state| config.state(state))
+                    .transpose()?,
+                dry_run: *dry_run,
+                target,
+                rev: rev.clone(),
+            };
+            let batch = project.cover(&options)?;
+            if *dry_run {
+                for path in batch.done.iter() {
+                    println!("would cover: {}", path.display());
                 }
-                Reset => {
-                    reset();
-                    Ok(())
+            }
+            for path in batch.skipped.iter() {
+                println!("skip: {} (translation missing)", path.display());
+            }
+            for (_, err) in batch.failed.iter() {
+                eprintln!("failed to cover: {err}");
+            }
+            match (dry_run, worktree.as_ref().or(out.as_ref())) {
+                (true, _) => {}
+                (false, Some(dir)) => {
+                    println!("{} files covered into {}.", batch.done.len(), dir.display())
                 }
-                Log => {
-                    println!("{}", get_log(&get_trans_dir()));
-                    Ok(())
+                (false, None) => println!("{} files covered.", batch.done.len()),
+            }
+            if !batch.failed.is_empty() {
+                return Err(GitTransError::Partial {
+                    failed: batch.failed.len(),
+                    total: batch.total(),
+                });
+            }
+            Ok(())
+        }
+        Build { command } => project.build(command),
+        Hooks { action } => {
+            match action {
+                HooksAction::Install { force } => {
+                    let hook = project.install_hooks(*force)?;
+                    println!("installed: {}", hook.display());
                 }
-                Todo => {
-                    println!("Need translate:");
-                    records.show_progress(Progress::Trans);
-                    println!("Need review:");
-                    records.show_progress(Progress::Review);
-                    println!("Need sync:");
-                    records.show_synced(false);
-                    Ok(())
+                HooksAction::Uninstall => match project.uninstall_hooks()? {
+                    Some(hook) => println!("removed: {}", hook.display()),
+                    None => println!("No pre-commit hook installed by git-trans."),
+                },
+            }
+            Ok(())
+        }
+        Reset { dry_run } => {
+            let files = project.reset(*dry_run)?;
+            let action = if *dry_run { "would reset" } else { "reset" };
+            for path in files.iter() {
+                println!("{action}: {}", path.display());
+            }
+            if files.is_empty() {
+                println!("No covered files to reset.");
+            }
+            Ok(())
+        }
+        Log => {
+            println!("{}", project.log()?);
+            Ok(())
+        }
+        Todo {
+            mine,
+            all_langs: false,
+        } => {
+            let mut records = project.records;
+            if *mine {
+                let user = current_user(&repo)?;
+                records.files.retain(|file| file.is_mine(config, &user));
+            }
+            match format {
+                Format::Text => show_todo(&repo, &records),
+                _ => println!("{}", render(&todo_rows(config, &records), format)),
+            }
+            Ok(())
+        }
+        Todo {
+            mine,
+            all_langs: true,
+        } => {
+            let mut langs = project.records.each_lang();
+            if *mine {
+                let user = current_user(&repo)?;
+                for records in langs.iter_mut() {
+                    records.files.retain(|file| file.is_mine(config, &user));
                 }
-                Show { status } => {
-                    match status {
-                        ShowStatus::All => records.show_all(),
-                        ShowStatus::Trans => records.show_progress(Progress::Trans),
-                        ShowStatus::Review => records.show_progress(Progress::Review),
-                        ShowStatus::Done => records.show_progress(Progress::Done),
-                        ShowStatus::Synced => records.show_synced(true),
-                        ShowStatus::Unsynced => records.show_synced(false),
-                        ShowStatus::Locked => records.show_locked(true),
-                        ShowStatus::Unlocked => records.show_locked(false),
-                    }
-                    Ok(())
+            }
+            if format != Format::Text {
+                let rows: Vec<LangRow<TodoRow>> = langs
+                    .iter()
+                    .flat_map(|records| {
+                        todo_rows(config, records).into_iter().map(|row| LangRow {
+                            row,
+                            lang: records.lang(),
+                        })
+                    })
+                    .collect();
+                println!("{}", render(&rows, format));
+                return Ok(());
+            }
+            for records in langs.iter() {
+                println!("{}", records.summary(config));
+                show_todo(&repo.for_records(records), records);
+                println!();
+            }
+            Ok(())
+        }
+        Show {
+            status,
+            all_langs: false,
+        } => {
+            match format {
+                Format::Text => show_status(&repo, &project.records, status),
+                _ => print_files(files_in_status(config, &project.records, status), format),
+            }
+            Ok(())
+        }
+        Show {
+            status,
+            all_langs: true,
+        } => {
+            let langs = project.records.each_lang();
+            if format != Format::Text {
+                let rows: Vec<LangRow<FileRow>> = langs
+                    .iter()
+                    .flat_map(|records| {
+                        files_in_status(config, records, status)
+                            .into_iter()
+                            .map(|file| LangRow {
+                                row: file.into(),
+                                lang: records.lang(),
+                            })
+                    })
+                    .collect();
+                println!("{}", render(&rows, format));
+                return Ok(());
+            }
+            for records in langs.iter() {
+                println!("{}", records.summary(config));
+                show_status(&repo.for_records(records), records, status);
+                println!();
+            }
+            Ok(())
+        }
+        Mark {
+            state,
+            path_args: path,
+            message,
+        } => {
+            let prog = config.state(state)?;
+            report("mark", project.mark(prog, path, message.as_deref())?)
+        }
+        Sync {
+            path_args: path,
+            merge,
+        } => {
+            if !*merge {
+                return report("sync", project.sync(path)?);
+            }
+            let batch = project.merge(path)?;
+            let mut done = Vec::new();
+            for (file, conflicts) in batch.done {
+                if conflicts > 0 {
+                    println!("conflict: {} ({conflicts} conflicts)", file.path.display());
                 }
-                Mark { status } => {
-                    match status {
-                        MarkProgress::Trans { path } => {
-                            records.mark_progress(Progress::Trans, path)?;
-                        }
-                        MarkProgress::Review { path } => {
-                            records.mark_progress(Progress::Review, path)?;
-                        }
-                        MarkProgress::Done { path } => {
-                            records.mark_progress(Progress::Done, path)?;
-                        }
+                done.push(file);
+            }
+            let batch = Batch {
+                done,
+                skipped: batch.skipped,
+                failed: batch.failed,
+            };
+            report("sync", batch)
+        }
+        Update { follow_renames } => {
+            for change in project.update(*follow_renames)? {
+                match change {
+                    UpstreamChange::Renamed {
+                        from,
+                        to,
+                        followed: true,
+                    } => println!("rename: {} -> {}", from.display(), to.display()),
+                    UpstreamChange::Renamed { from, to, .. } => println!(
+                        "orphaned: {} (renamed to {}, update with --follow-renames to move it)",
+                        from.display(),
+                        to.display()
+                    ),
+                    UpstreamChange::Deleted(path) => {
+                        println!("orphaned: {} (deleted upstream)", path.display())
                     }
-                    Ok(())
-                }
-                Sync { path_args: path } => {
-                    let path = &path[0].to_path_buf();
-                    records.set_synced(path);
-                    Ok(())
-                }
-                Update => {
-                    records.update_sync();
-                    Ok(())
-                }
-                Lock { path_args: path } => {
-                    let path = &path[0].to_path_buf();
-                    records.set_lock(true, path)?;
-                    Ok(())
-                }
-                Unlock { path_args: path } => {
-                    let path = &path[0].to_path_buf();
-                    records.set_lock(false, path)?;
-                    Ok(())
                 }
+            }
+            Ok(())
+        }
+        Assign {
+            user,
+            path_args: path,
+            reviewer,
+            message,
+        } => report(
+            "assign",
+            project.assign(user, *reviewer, path, message.as_deref())?,
+        ),
+        Lock {
+            path_args: path,
+            message,
+        } => report("lock", project.lock(true, path, message.as_deref())?),
+        Unlock {
+            path_args: path,
+            message,
+        } => report("unlock", project.lock(false, path, message.as_deref())?),
+        Init { .. } => unreachable!("init is handled before opening the project"),
+        CheckCommit => unreachable!("check-commit is handled before opening the project"),
+    }
+}
+
+/// Email of the git user, files are assigned to users by their emails
+fn current_user(repo: &Repo) -> Result<String> {
+    get_user_email(&repo.root_dir)
+        .ok_or_else(|| GitTransError::Config("user.email is not set in git config".to_string()))
+}
+
+/// States of the workflow listed by todo
+fn todo_states(config: &Config) -> Vec<Progress> {
+    config
+        .workflow
+        .iter()
+        .filter(|state| state.todo)
+        .map(|state| Progress::new(&state.name))
+        .collect()
+}
+
+/// Todo rows of the selected files, in the todo states of the workflow, to sync or resolve
+fn todo_rows<'a>(config: &Config, records: &'a Records) -> Vec<TodoRow<'a>> {
+    let mut todo: Vec<(String, Vec<&TrackedFile>)> = todo_states(config)
+        .into_iter()
+        .map(|prog| (prog.0.to_lowercase(), records.with_progress(&prog)))
+        .collect();
+    todo.push(("unsynced".to_string(), records.with_synced(false)));
+    todo.push(("orphaned".to_string(), records.with_orphaned()));
+    todo.push(("unknown".to_string(), records.with_unknown_progress(config)));
+    todo.into_iter()
+        .flat_map(|(todo, files)| {
+            files.into_iter().map(move |file| TodoRow {
+                todo: todo.clone(),
+                file: file.into(),
+            })
+        })
+        .collect()
+}
 
-                _ => todo!(),
+/// Show the todo list of the selected files
+fn show_todo(repo: &Repo, records: &Records) {
+    for prog in todo_states(&repo.config) {
+        println!("Need {}:", prog.0.to_lowercase());
+        show_progress(&repo.config, records, prog);
+    }
+    println!("Need sync:");
+    show_synced(repo, records, false);
+    println!("Deleted or renamed upstream:");
+    show_orphaned(records);
+    println!("Unknown state:");
+    show_unknown(&repo.config, records);
+}
+
+/// Progress of a state checked against the workflow, in the case of the workflow
+fn state_progress(config: &Config, name: &str) -> Progress {
+    config.state(name).unwrap_or_else(|_| Progress::new(name))
+}
+
+/// Selected files in a status
+fn files_in_status<'a>(
+    config: &Config,
+    records: &'a Records,
+    status: &ShowStatus,
+) -> Vec<&'a TrackedFile> {
+    match status {
+        ShowStatus::All => records.files.iter().collect(),
+        ShowStatus::State(name) => records.with_progress(&state_progress(config, name)),
+        ShowStatus::Synced => records.with_synced(true),
+        ShowStatus::Unsynced => records.with_synced(false),
+        ShowStatus::Orphaned => records.with_orphaned(),
+        ShowStatus::Unknown => records.with_unknown_progress(config),
+        ShowStatus::Locked => records.with_locked(true),
+        ShowStatus::Unlocked => records.with_locked(false),
+    }
+}
+
+/// Show the selected files in a status
+fn show_status(repo: &Repo, records: &Records, status: &ShowStatus) {
+    let config = &repo.config;
+    match status {
+        ShowStatus::All => show_all(config, records),
+        ShowStatus::State(name) => show_progress(config, records, state_progress(config, name)),
+        ShowStatus::Synced => show_synced(repo, records, true),
+        ShowStatus::Unsynced => show_synced(repo, records, false),
+        ShowStatus::Orphaned => show_orphaned(records),
+        ShowStatus::Unknown => show_unknown(config, records),
+        ShowStatus::Locked => show_locked(records, true),
+        ShowStatus::Unlocked => show_locked(records, false),
+    }
+}
+
+/// Print tracked files in a machine readable format
+fn print_files(files: Vec<&TrackedFile>, format: Format) {
+    let rows: Vec<FileRow> = files.into_iter().map(FileRow::from).collect();
+    println!("{}", render(&rows, format));
+}
+
+/// Print the translated segments of a file outdated by the latest source to stderr
+fn print_outdated(repo: &Repo, file: &TrackedFile) {
+    let Some(outdated) = file.outdated_segments(repo).filter(|o| !o.is_empty()) else {
+        return;
+    };
+    let trans_path = repo.trans_dir_rel().join(&file.path);
+    eprintln!("outdated segments of {}:", trans_path.display());
+    let lines = |(first, last)| match first == last {
+        true => format!("line {first}"),
+        false => format!("lines {first}-{last}"),
+    };
+    for segment in outdated {
+        match (segment.trans, segment.source) {
+            (Some(trans), Some(source)) => {
+                eprintln!("  {} (source {} changed)", lines(trans), lines(source))
             }
+            (Some(trans), None) => eprintln!("  {} (removed from source)", lines(trans)),
+            (None, Some(source)) => eprintln!(
+                "  after line {} (source {} added)",
+                segment.after,
+                lines(source)
+            ),
+            (None, None) => {}
         }
     }
 }
+
+/// Report the result of a batch per file, returns the error itself for a single path,
+/// or a partial error if any of them failed
+fn report(action: &str, batch: Batch) -> Result<()> {
+    report_with(action, batch, |file| {
+        println!("{action}: {}", file.path.display())
+    })
+}
+
+/// Report the results of a batch, showing each result with a function
+fn report_with<T, F>(action: &str, mut batch: Batch<T>, show_fn: F) -> Result<()>
+where
+    F: Fn(&T),
+{
+    let total = batch.total();
+    if total == 1
+        && let Some((_, err)) = batch.failed.pop()
+    {
+        return Err(err);
+    }
+
+    for done in batch.done.iter() {
+        show_fn(done);
+    }
+    for path in batch.skipped.iter() {
+        println!("skip: {} (already recorded)", path.display());
+    }
+    for (_, err) in batch.failed.iter() {
+        eprintln!("failed to {action}: {err}");
+    }
+    if !batch.failed.is_empty() {
+        return Err(GitTransError::Partial {
+            failed: batch.failed.len(),
+            total,
+        });
+    }
+    Ok(())
+}
@@ -0,0 +1,340 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::diff::unwrap_paragraphs;
+use crate::segment::{Block, Op, align_blocks, diff, is_markdown, split_blocks};
+
+/// Result of merging upstream changes into a translation
+#[derive(Debug, Clone, PartialEq)]
+pub struct Merged {
+    /// The merged translation
+    pub content: String,
+    /// Number of conflicts left with markers in the content
+    pub conflicts: usize,
+}
+
+/// Split the link targets out of a text, returns the text without them and the targets
+fn split_links(text: &str) -> (String, Vec<&str>) {
+    let mut stripped = String::new();
+    let mut targets = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("](") {
+        let Some(len) = rest[start + 2..].find(')') else {
+            break;
+        };
+        stripped.push_str(&rest[..start + 2]);
+        targets.push(&rest[start + 2..start + 2 + len]);
+        rest = &rest[start + 2 + len..];
+    }
+    stripped.push_str(rest);
+    (stripped, targets)
+}
+
+/// Apply the link target changes from `base` to `upstream` onto a translation,
+/// `None` if the texts differ in more than link targets
+fn relink(base: &str, upstream: &str, translation: &str) -> Option<String> {
+    let (base_text, base_targets) = split_links(base);
+    let (upstream_text, upstream_targets) = split_links(upstream);
+    if base_text != upstream_text {
+        return None;
+    }
+    let mut relinked = translation.to_string();
+    for (old, new) in base_targets.into_iter().zip(upstream_targets) {
+        if old != new {
+            let old = format!("]({old})");
+            if !relinked.contains(&old) {
+                return None;
+            }
+            relinked = relinked.replace(&old, &format!("]({new})"));
+        }
+    }
+    Some(relinked)
+}
+
+/// Text of a block to compare, prose has its soft line breaks joined so that
+/// a reflowed block is equal to the original
+fn compared_text(path: &Path, block: &Block) -> String {
+    match block.is_code() {
+        true => block.text().to_string(),
+        false => unwrap_paragraphs(path, block.text()),
+    }
+}
+
+/// Similarity of two texts from 0 to 1, the Dice coefficient of their character bigrams
+fn similarity(a: &str, b: &str) -> f64 {
+    let bigrams = |text: &str| {
+        let chars: Vec<char> = text
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .chars()
+            .collect();
+        let mut counts: HashMap<(char, char), usize> = HashMap::new();
+        for pair in chars.windows(2) {
+            *counts.entry((pair[0], pair[1])).or_default() += 1;
+        }
+        counts
+    };
+    let (a_bigrams, b_bigrams) = (bigrams(a), bigrams(b));
+    let total: usize = a_bigrams.values().chain(b_bigrams.values()).sum();
+    if total == 0 {
+        return if a.trim() == b.trim() { 1.0 } else { 0.0 };
+    }
+    let common: usize = a_bigrams
+        .iter()
+        .map(|(bigram, n)| (*n).min(b_bigrams.get(bigram).copied().unwrap_or(0)))
+        .sum();
+    2.0 * common as f64 / total as f64
+}
+
+/// Pair the items of two lists in order, maximizing the total score of the pairs.
+/// Items scored `None` are never paired
+fn pair<F>(n: usize, m: usize, score: F) -> Vec<Op>
+where
+    F: Fn(usize, usize) -> Option<f64>,
+{
+    // best[i][j] is the best total score of pairing a[i..] with b[j..]
+    let mut best = vec![vec![0.0f64; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            let paired = score(i, j).map_or(f64::MIN, |s| best[i + 1][j + 1] + s);
+            best[i][j] = paired.max(best[i + 1][j]).max(best[i][j + 1]);
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut ops = Vec::new();
+    while i < n || j < m {
+        if i < n && j < m && score(i, j).is_some_and(|s| best[i + 1][j + 1] + s >= best[i][j]) {
+            ops.push(Op::Equal(i, j));
+            (i, j) = (i + 1, j + 1);
+        } else if j < m && (i == n || best[i][j + 1] >= best[i + 1][j]) {
+            ops.push(Op::Insert(j));
+            j += 1;
+        } else {
+            ops.push(Op::Delete(i));
+            i += 1;
+        }
+    }
+    ops
+}
+
+/// Append a block to the output, separated from the previous one by a blank line
+fn push_block(out: &mut String, raw: &str) {
+    if raw.is_empty() {
+        return;
+    }
+    while !out.is_empty() && !out.ends_with("\n\n") {
+        out.push('\n');
+    }
+    out.push_str(raw);
+}
+
+/// Merge the upstream changes of a source from `base` to `upstream` into its translation.
+///
+/// The source and the translation are split into segments and aligned by their
+/// structure, then the structural changes are applied to the translation: removed blocks
+/// are dropped, added blocks are inserted untranslated, moved blocks keep their translation,
+/// and changed code blocks and link targets are updated. Changed prose, paired with the
+/// most similar removed block, is left between conflict markers with the new source text.
+/// Prose only reflowed upstream is unchanged
+pub fn merge(path: &Path, base: &str, translation: &str, upstream: &str) -> Merged {
+    let markdown = is_markdown(path);
+    let base_blocks = split_blocks(base, markdown);
+    let trans_blocks = split_blocks(translation, markdown);
+    let upstream_blocks = split_blocks(upstream, markdown);
+
+    // blocks only in the translation stay after the block before them
+    let (trans_of, extra) = align_blocks(&base_blocks, &trans_blocks);
+    let translated = |i: usize, with_block: bool| -> Vec<&str> {
+        let block = trans_of[i].filter(|_| with_block);
+        block
+            .into_iter()
+            .chain(extra.get(&Some(i)).into_iter().flatten().copied())
+            .map(|j| trans_blocks[j].raw)
+            .collect()
+    };
+
+    let base_texts: Vec<String> = base_blocks.iter().map(|b| compared_text(path, b)).collect();
+    let upstream_texts: Vec<String> = upstream_blocks
+        .iter()
+        .map(|b| compared_text(path, b))
+        .collect();
+    let ops = diff(&base_texts, &upstream_texts, |b, u| b == u);
+    // blocks deleted in one place and inserted in another are moved with their translation
+    let mut deleted: HashMap<&str, Vec<usize>> = HashMap::new();
+    for op in ops.iter().rev() {
+        if let Op::Delete(i) = op {
+            deleted.entry(&base_texts[*i]).or_default().push(*i);
+        }
+    }
+    let mut moved_to: HashMap<usize, usize> = HashMap::new();
+    for op in ops.iter() {
+        if let Op::Insert(j) = op
+            && let Some(i) = deleted
+                .get_mut(upstream_texts[*j].as_str())
+                .and_then(|bases| bases.pop())
+        {
+            moved_to.insert(*j, i);
+        }
+    }
+    let moved_from: Vec<usize> = moved_to.values().copied().collect();
+
+    let mut out = String::new();
+    let mut conflicts = 0;
+    for raw in extra.get(&None).into_iter().flatten() {
+        push_block(&mut out, trans_blocks[*raw].raw);
+    }
+    let mut pos = 0;
+    while pos < ops.len() {
+        if let Op::Equal(i, _) = ops[pos] {
+            for raw in translated(i, true) {
+                push_block(&mut out, raw);
+            }
+            pos += 1;
+            continue;
+        }
+        // a hunk of consecutive deletions and insertions
+        let end = ops[pos..]
+            .iter()
+            .position(|op| matches!(op, Op::Equal(..)))
+            .map_or(ops.len(), |len| pos + len);
+        let mut removed = Vec::new();
+        let mut added = Vec::new();
+        for op in &ops[pos..end] {
+            match *op {
+                Op::Delete(i) if !moved_from.contains(&i) => removed.push(i),
+                Op::Insert(j) if !moved_to.contains_key(&j) => added.push(j),
+                _ => {}
+            }
+        }
+        // pair the removed and added blocks of the same kinds by their similarity
+        // to apply the changes one by one
+        let mut text = String::new();
+        let score = |r: usize, a: usize| {
+            let (i, j) = (removed[r], added[a]);
+            (base_blocks[i].kind == upstream_blocks[j].kind)
+                .then(|| similarity(&base_texts[i], &upstream_texts[j]))
+        };
+        for op in pair(removed.len(), added.len(), score) {
+            match op {
+                Op::Equal(r, a) => {
+                    let (base_block, upstream_block) =
+                        (&base_blocks[removed[r]], &upstream_blocks[added[a]]);
+                    let relinked = trans_of[removed[r]].and_then(|t| {
+                        relink(
+                            &base_texts[removed[r]],
+                            &upstream_texts[added[a]],
+                            trans_blocks[t].raw,
+                        )
+                    });
+                    if base_block.is_code() {
+                        // code is not translated, take the new code
+                        push_block(&mut text, upstream_block.raw);
+                    } else if let Some(relinked) = relinked {
+                        push_block(&mut text, &relinked);
+                    } else {
+                        let ours = trans_of[removed[r]].map_or("", |t| trans_blocks[t].raw);
+                        let ours = match ours.trim_end() {
+                            "" => String::new(),
+                            ours => format!("{ours}\n"),
+                        };
+                        push_block(
+                            &mut text,
+                            &format!(
+                                "<<<<<<< translation\n{ours}=======\n{}\n>>>>>>> upstream\n",
+                                upstream_block.text()
+                            ),
+                        );
+                        conflicts += 1;
+                    }
+                    // blocks only in the translation are kept after their block
+                    translated(removed[r], false)
+                        .into_iter()
+                        .for_each(|raw| push_block(&mut text, raw));
+                }
+                // removed blocks drop their translation but keep the blocks only in the translation
+                Op::Delete(r) => translated(removed[r], false)
+                    .into_iter()
+                    .for_each(|raw| push_block(&mut text, raw)),
+                Op::Insert(a) => push_block(&mut text, upstream_blocks[added[a]].raw),
+            }
+        }
+        // the changed text goes where the hunk starts, moved blocks where they are inserted
+        let mut text = Some(text);
+        for op in &ops[pos..end] {
+            match *op {
+                Op::Insert(j) if moved_to.contains_key(&j) => {
+                    for raw in translated(moved_to[&j], true) {
+                        push_block(&mut out, raw);
+                    }
+                }
+                _ => {
+                    if let Some(text) = text.take() {
+                        push_block(&mut out, &text);
+                    }
+                }
+            }
+        }
+        pos = end;
+    }
+    let content = format!("{}\n", out.trim_end());
+    Merged { content, conflicts }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn merge_md(base: &str, translation: &str, upstream: &str) -> Merged {
+        merge(Path::new("doc.md"), base, translation, upstream)
+    }
+
+    #[test]
+    fn insert_before_change() {
+        let merged = merge_md(
+            "# Title\n\nThe tool copies the files.\n",
+            "# 标题\n\n该工具复制文件。\n",
+            "# Title\n\nA new introduction.\n\nThe tool copies all the files.\n",
+        );
+        assert_eq!(
+            merged.content,
+            "# 标题\n\nA new introduction.\n\n<<<<<<< translation\n该工具复制文件。\n\
+             =======\nThe tool copies all the files.\n>>>>>>> upstream\n"
+        );
+        assert_eq!(merged.conflicts, 1);
+    }
+
+    #[test]
+    fn pure_rewrap() {
+        let translation = "# 标题\n\n第一段，\n很长。\n";
+        let merged = merge_md(
+            "# Title\n\nThe first paragraph\nis long.\n",
+            translation,
+            "# Title\n\nThe first\nparagraph is long.\n",
+        );
+        assert_eq!(merged.content, translation);
+        assert_eq!(merged.conflicts, 0);
+    }
+
+    #[test]
+    fn moved_blocks() {
+        let merged = merge_md(
+            "# Alpha\n\nFirst.\n\n# Beta\n\nSecond.\n",
+            "# 甲\n\n第一。\n\n# 乙\n\n第二。\n",
+            "# Beta\n\nSecond.\n\n# Alpha\n\nFirst.\n",
+        );
+        assert_eq!(merged.content, "# 乙\n\n第二。\n\n# 甲\n\n第一。\n");
+        assert_eq!(merged.conflicts, 0);
+    }
+
+    #[test]
+    fn link_only_change() {
+        let merged = merge_md(
+            "See [the guide](old.md) first.\n",
+            "请先阅读[指南](old.md)。\n",
+            "See [the guide](new.md) first.\n",
+        );
+        assert_eq!(merged.content, "请先阅读[指南](new.md)。\n");
+        assert_eq!(merged.conflicts, 0);
+    }
+}
@@ -0,0 +1,314 @@
+use clap::ValueEnum;
+use serde::Serialize;
+use std::path::Path;
+
+use crate::records::{FileInfo, HistoryEntry, Progress, TrackedFile};
+use crate::stats::{Group, Stats};
+
+/// Output format of the listing commands
+#[derive(Debug, Clone, Copy, PartialEq, Default, ValueEnum)]
+pub enum Format {
+    /// Coloured text for humans
+    #[default]
+    Text,
+    /// JSON array of objects
+    Json,
+    /// Comma separated values with a header line
+    Csv,
+    /// Tab separated values with a header line
+    Tsv,
+}
+
+/// A row of machine readable output. The schema is stable,
+/// new columns are only ever appended after the existing ones
+pub trait Row: Serialize {
+    /// Column names of csv and tsv output
+    fn header() -> Vec<&'static str>;
+    /// Column values of csv and tsv output, in the order of the header
+    fn values(&self) -> Vec<String>;
+}
+
+/// A tracked file, the columns are `path`, `track_rev`, `progress`, `synced`, `locked`,
+/// `source_blob`, `orphaned`, `assignee` and `reviewer`
+#[derive(Debug, Serialize)]
+pub struct FileRow<'a> {
+    pub path: &'a Path,
+    pub track_rev: &'a str,
+    pub progress: &'a Progress,
+    pub synced: bool,
+    pub locked: bool,
+    pub source_blob: Option<&'a str>,
+    pub orphaned: bool,
+    pub assignee: Option<&'a str>,
+    pub reviewer: Option<&'a str>,
+}
+
+impl<'a> From<&'a TrackedFile> for FileRow<'a> {
+    fn from(file: &'a TrackedFile) -> Self {
+        FileRow {
+            path: &file.path,
+            track_rev: &file.track_rev,
+            progress: &file.progress,
+            synced: file.synced,
+            locked: file.locked == Some(true),
+            source_blob: file.source_blob.as_deref(),
+            orphaned: file.orphaned == Some(true),
+            assignee: file.assignee.as_deref(),
+            reviewer: file.reviewer.as_deref(),
+        }
+    }
+}
+
+impl Row for FileRow<'_> {
+    fn header() -> Vec<&'static str> {
+        vec![
+            "path",
+            "track_rev",
+            "progress",
+            "synced",
+            "locked",
+            "source_blob",
+            "orphaned",
+            "assignee",
+            "reviewer",
+        ]
+    }
+
+    fn values(&self) -> Vec<String> {
+        vec![
+            self.path.display().to_string(),
+            self.track_rev.to_string(),
+            self.progress.to_string(),
+            self.synced.to_string(),
+            self.locked.to_string(),
+            self.source_blob.unwrap_or_default().to_string(),
+            self.orphaned.to_string(),
+            self.assignee.unwrap_or_default().to_string(),
+            self.reviewer.unwrap_or_default().to_string(),
+        ]
+    }
+}
+
+/// A tracked file in a todo list, the `todo` column is one of the todo states of the
+/// workflow in lowercase, like `trans` and `review`, or `unsynced` and `orphaned`,
+/// followed by the columns of [`FileRow`]
+#[derive(Debug, Serialize)]
+pub struct TodoRow<'a> {
+    pub todo: String,
+    #[serde(flatten)]
+    pub file: FileRow<'a>,
+}
+
+impl Row for TodoRow<'_> {
+    fn header() -> Vec<&'static str> {
+        let mut header = vec!["todo"];
+        header.extend(FileRow::header());
+        header
+    }
+
+    fn values(&self) -> Vec<String> {
+        let mut values = vec![self.todo.clone()];
+        values.extend(self.file.values());
+        values
+    }
+}
+
+/// A row of one of several languages, the `lang` column is appended to the columns of the row
+#[derive(Debug, Serialize)]
+pub struct LangRow<'a, R: Row> {
+    #[serde(flatten)]
+    pub row: R,
+    pub lang: &'a str,
+}
+
+impl<R: Row> Row for LangRow<'_, R> {
+    fn header() -> Vec<&'static str> {
+        let mut header = R::header();
+        header.push("lang");
+        header
+    }
+
+    fn values(&self) -> Vec<String> {
+        let mut values = self.row.values();
+        values.push(self.lang.to_string());
+        values
+    }
+}
+
+/// A transition in the history of a file, the columns are
+/// `datetime`, `author`, `action` and `message`
+impl Row for HistoryEntry {
+    fn header() -> Vec<&'static str> {
+        vec!["datetime", "author", "action", "message"]
+    }
+
+    fn values(&self) -> Vec<String> {
+        vec![
+            self.datetime.clone(),
+            self.author.clone().unwrap_or_default(),
+            self.action.clone(),
+            self.message.clone().unwrap_or_default(),
+        ]
+    }
+}
+
+/// All information of a tracked file, the columns are the fields of [`FileInfo`]
+impl Row for FileInfo {
+    fn header() -> Vec<&'static str> {
+        vec![
+            "path",
+            "track_rev",
+            "track_date",
+            "track_subject",
+            "upstream_rev",
+            "commits_since",
+            "progress",
+            "locked",
+            "synced",
+            "copy",
+            "source_blob",
+            "upstream_blob",
+            "orphaned",
+            "outdated_segments",
+            "assignee",
+            "reviewer",
+            "review_requester",
+        ]
+    }
+
+    fn values(&self) -> Vec<String> {
+        vec![
+            self.path.display().to_string(),
+            self.track_rev.clone(),
+            self.track_date.clone().unwrap_or_default(),
+            self.track_subject.clone().unwrap_or_default(),
+            self.upstream_rev.clone().unwrap_or_default(),
+            self.commits_since.to_string(),
+            self.progress.to_string(),
+            self.locked.to_string(),
+            self.synced.to_string(),
+            self.copy.to_string(),
+            self.source_blob.clone().unwrap_or_default(),
+            self.upstream_blob.clone().unwrap_or_default(),
+            self.orphaned.to_string(),
+            self.outdated_segments
+                .map(|count| count.to_string())
+                .unwrap_or_default(),
+            self.assignee.clone().unwrap_or_default(),
+            self.reviewer.clone().unwrap_or_default(),
+            self.review_requester.clone().unwrap_or_default(),
+        ]
+    }
+}
+
+/// The tally of a progress in a group of files, the columns are `by`, one of `total`,
+/// `dir` and `assignee`, `name` of the directory or assignee, `progress`, `files`, `words`,
+/// `chars`, `lines`, `percent` of the group weighted by source characters, `unsynced` and `backlog`
+#[derive(Debug, Serialize)]
+pub struct StatRow<'a> {
+    pub by: &'static str,
+    pub name: &'a str,
+    pub progress: &'a Progress,
+    pub files: usize,
+    pub words: usize,
+    pub chars: usize,
+    pub lines: usize,
+    pub percent: f64,
+    pub unsynced: usize,
+    pub backlog: usize,
+}
+
+impl Row for StatRow<'_> {
+    fn header() -> Vec<&'static str> {
+        vec![
+            "by", "name", "progress", "files", "words", "chars", "lines", "percent", "unsynced",
+            "backlog",
+        ]
+    }
+
+    fn values(&self) -> Vec<String> {
+        vec![
+            self.by.to_string(),
+            self.name.to_string(),
+            self.progress.to_string(),
+            self.files.to_string(),
+            self.words.to_string(),
+            self.chars.to_string(),
+            self.lines.to_string(),
+            format!("{:.1}", self.percent),
+            self.unsynced.to_string(),
+            self.backlog.to_string(),
+        ]
+    }
+}
+
+/// Rows of the statistics, a row for each progress of the total, each directory and each assignee
+pub fn stat_rows(stats: &Stats) -> Vec<StatRow<'_>> {
+    let groups = std::iter::once(("total", &stats.total))
+        .chain(stats.dirs.iter().map(|group| ("dir", group)))
+        .chain(stats.assignees.iter().map(|group| ("assignee", group)));
+    groups
+        .flat_map(|(by, group): (&'static str, &Group)| {
+            group.progress.iter().map(move |(progress, tally)| StatRow {
+                by,
+                name: &group.name,
+                progress,
+                files: tally.files,
+                words: tally.words,
+                chars: tally.chars,
+                lines: tally.lines,
+                percent: group.percent(progress),
+                unsynced: tally.unsynced,
+                backlog: tally.backlog,
+            })
+        })
+        .collect()
+}
+
+/// Render rows in a machine readable format without colour,
+/// text is rendered as tab separated values
+pub fn render<R: Row>(rows: &[R], format: Format) -> String {
+    match format {
+        Format::Json => serde_json::to_string_pretty(rows).expect("rows are always serializable"),
+        Format::Csv => render_delimited(rows, b','),
+        Format::Text | Format::Tsv => render_delimited(rows, b'\t'),
+    }
+}
+
+/// Render a single row, as a JSON object rather than an array
+pub fn render_one<R: Row>(row: &R, format: Format) -> String {
+    match format {
+        Format::Json => serde_json::to_string_pretty(row).expect("rows are always serializable"),
+        _ => render(std::slice::from_ref(row), format),
+    }
+}
+
+/// Render rows as delimiter separated values with a header line
+fn render_delimited<R: Row>(rows: &[R], delimiter: u8) -> String {
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(delimiter)
+        .quote_style(if delimiter == b'\t' {
+            csv::QuoteStyle::Never
+        } else {
+            csv::QuoteStyle::Necessary
+        })
+        .from_writer(Vec::new());
+    // tab separated values can not quote, so tabs and newlines are replaced by spaces
+    let clean = |value: String| {
+        if delimiter == b'\t' {
+            value.replace(['\t', '\n', '\r'], " ")
+        } else {
+            value
+        }
+    };
+    writer
+        .write_record(R::header())
+        .expect("writing to memory never fails");
+    for row in rows {
+        writer
+            .write_record(row.values().into_iter().map(clean))
+            .expect("writing to memory never fails");
+    }
+    let output = writer.into_inner().expect("writing to memory never fails");
+    String::from_utf8_lossy(&output).trim_end().to_string()
+}
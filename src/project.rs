@@ -0,0 +1,690 @@
+use glob::Pattern;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::config::{CONFIG_FILE, Config, CoverStrategy};
+use crate::diff::{DiffOptions, side_by_side, terminal_width};
+use crate::errors::{GitTransError, Result};
+use crate::git::*;
+use crate::merge::merge;
+use crate::records::{Progress, Records, TrackedFile, UpstreamChange};
+use crate::utils::*;
+
+/// A git repository translated with git-trans
+#[derive(Debug)]
+pub struct Project {
+    /// Root directory and configuration of the repository
+    pub repo: Repo,
+    /// Loaded records
+    pub records: Records,
+}
+
+/// Root directory and configuration of a repository, passed to the records and helpers
+/// so that several repositories can be opened in one process
+#[derive(Debug, Clone)]
+pub struct Repo {
+    /// Root directory of the git repository
+    pub root_dir: PathBuf,
+    /// Loaded configuration
+    pub config: Config,
+    /// Directory of the selected language in the translation directory,
+    /// empty for the default language
+    pub lang_dir: PathBuf,
+}
+
+impl Repo {
+    /// Find the repository containing a directory and load its configuration,
+    /// translating into the default language
+    pub fn open(dir: &Path) -> Result<Repo> {
+        let root_dir = get_root_dir(dir).ok_or(GitTransError::NotARepo)?;
+        let config = Config::load(&root_dir)?;
+        Ok(Repo {
+            root_dir,
+            config,
+            lang_dir: PathBuf::new(),
+        })
+    }
+
+    /// The repository translating into the selected language of some records
+    pub fn for_records(&self, records: &Records) -> Repo {
+        Repo {
+            lang_dir: records.lang_dir(records.lang()),
+            ..self.clone()
+        }
+    }
+
+    /// Translation directory of the selected language relative to the root directory,
+    /// `.trans` for the default language and `.trans/<lang>` for the others
+    pub fn trans_dir_rel(&self) -> PathBuf {
+        Path::new(&self.config.trans_dir).join(&self.lang_dir)
+    }
+
+    /// Translation directory of the selected language
+    pub fn trans_dir(&self) -> PathBuf {
+        self.root_dir.join(self.trans_dir_rel())
+    }
+
+    /// Records file, shared by all languages
+    pub fn records_file(&self) -> PathBuf {
+        self.root_dir
+            .join(&self.config.trans_dir)
+            .join(&self.config.records_file)
+    }
+}
+
+/// Marker line of the hooks installed by git-trans
+const HOOK_MARKER: &str = "# installed by git-trans";
+
+/// Where translations are covered into
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum CoverTarget {
+    /// The root directory of the repository
+    #[default]
+    Root,
+    /// A detached git worktree at the tracked revision
+    Worktree(PathBuf),
+    /// A directory the tracked revision is exported into
+    Out(PathBuf),
+}
+
+/// Options of covering translations into the root directory
+#[derive(Debug, Clone, Default)]
+pub struct CoverOptions {
+    /// Only cover files with at least this progress
+    pub min_progress: Option<Progress>,
+    /// Only find the files that would change, without copying them
+    pub dry_run: bool,
+    /// Where to cover the translations
+    pub target: CoverTarget,
+    /// Revision checked out in a worktree or exported, the tracked revision by default
+    pub rev: Option<String>,
+}
+
+/// Result of an operation on several paths
+#[derive(Debug)]
+pub struct Batch<T = TrackedFile> {
+    /// Results of the paths the operation succeeded on
+    pub done: Vec<T>,
+    /// Paths skipped by the operation, relative to the root directory
+    pub skipped: Vec<PathBuf>,
+    /// Paths the operation failed on with their errors
+    pub failed: Vec<(PathBuf, GitTransError)>,
+}
+
+impl<T> Batch<T> {
+    /// Run an action on each path and collect the results
+    fn run<F>(paths: &[PathBuf], mut action_fn: F) -> Batch<T>
+    where
+        F: FnMut(&Path) -> Result<T>,
+    {
+        let mut batch = Batch {
+            done: Vec::new(),
+            skipped: Vec::new(),
+            failed: Vec::new(),
+        };
+        for path in paths {
+            match action_fn(path) {
+                Ok(done) => batch.done.push(done),
+                Err(err) => batch.failed.push((path.to_path_buf(), err)),
+            }
+        }
+        batch
+    }
+
+    /// Total number of paths in the batch
+    pub fn total(&self) -> usize {
+        self.done.len() + self.skipped.len() + self.failed.len()
+    }
+}
+
+impl Project {
+    /// Open the project of the current directory, loading the configuration and records
+    pub fn open() -> Result<Project> {
+        Project::open_lang(None)
+    }
+
+    /// Open the project of the current directory translating into a language,
+    /// the default language of the records if `None`
+    pub fn open_lang(lang: Option<&str>) -> Result<Project> {
+        Project::open_at(Path::new("."), lang)
+    }
+
+    /// Open the project of the repository containing a directory translating into a language,
+    /// the default language of the records if `None`
+    pub fn open_at(dir: &Path, lang: Option<&str>) -> Result<Project> {
+        let repo = Repo::open(dir)?;
+        let records = Records::load(&repo)?;
+        let mut project = Project { repo, records };
+        if let Some(lang) = lang {
+            project.select(lang)?;
+        }
+        Ok(project)
+    }
+
+    /// Select the files of a language, translated in its own directory
+    pub fn select(&mut self, lang: &str) -> Result<()> {
+        self.records.select(lang)?;
+        self.repo = self.repo.for_records(&self.records);
+        Ok(())
+    }
+
+    /// Initialize the project of the current directory, creating the records file.
+    /// An initialized project gets another language, translated in `.trans/<lang>`,
+    /// the tag must then be `HEAD` as the files of a language track their own revisions
+    pub fn init(lang: &str, tag: &String) -> Result<Project> {
+        Project::init_at(Path::new("."), lang, tag)
+    }
+
+    /// Initialize the project of the repository containing a directory, like [`Project::init`]
+    pub fn init_at(dir: &Path, lang: &str, tag: &String) -> Result<Project> {
+        let repo = Repo::open(dir)?;
+        if repo.records_file().is_file() {
+            if tag != "HEAD" {
+                return Err(GitTransError::LangTag(tag.clone()));
+            }
+            let mut records = Records::load(&repo)?;
+            records.add_lang(lang)?;
+            records.save(&repo)?;
+            let project = Project::open_at(dir, Some(lang))?;
+            fs::create_dir_all(project.repo.trans_dir())?;
+            return Ok(project);
+        }
+        let records = Records::init(&repo, lang, tag)?;
+        let content = toml::to_string(&records).expect("records are always serializable");
+        let mut file = create_file_with_dirs(repo.records_file())?;
+        file.write_all(content.as_bytes())?;
+        Ok(Project { repo, records })
+    }
+
+    /// Save records to the records file
+    pub fn save(&self) -> Result<()> {
+        self.records.save(&self.repo)
+    }
+
+    /// Get a path relative to the root directory for reporting
+    fn rel_to_root(&self, path: &Path) -> PathBuf {
+        absolute_to_relative(&self.repo.root_dir, path).unwrap_or(path.to_path_buf())
+    }
+
+    /// Add git tracked files, directories and globs to the records and copy them
+    /// into the translation directory, files already recorded are skipped.
+    /// The configured include patterns and lock state are used as defaults
+    pub fn add(
+        &mut self,
+        paths: &[PathBuf],
+        lock: bool,
+        include: &[Pattern],
+        exclude: &[Pattern],
+    ) -> Result<Batch> {
+        let include = if include.is_empty() {
+            &self.repo.config.include_patterns()?
+        } else {
+            include
+        };
+        let lock = lock || self.repo.config.lock;
+        let (recorded, added): (Vec<PathBuf>, Vec<PathBuf>) =
+            expand_tracked_paths(&self.repo, paths, include, exclude)?
+                .into_iter()
+                .partition(|path| path.exists() && self.records.contains(&self.repo, path));
+        let mut batch = Batch::run(&added, |path| {
+            let added_file = self.records.add(&self.repo, path, lock)?;
+            if let Err(err) = copy_file_to_trans(&self.repo, path) {
+                self.records.remove(&self.repo, path)?;
+                return Err(err);
+            }
+            Ok(added_file)
+        });
+        batch.skipped = recorded.iter().map(|path| self.rel_to_root(path)).collect();
+        self.save()?;
+        Ok(batch)
+    }
+
+    /// Remove files from the records
+    pub fn remove(&mut self, paths: &[PathBuf]) -> Result<Batch> {
+        let batch = Batch::run(&self.records.expand(&self.repo, paths)?, |path| {
+            self.records.remove(&self.repo, path)
+        });
+        self.save()?;
+        Ok(batch)
+    }
+
+    /// Mark the progress of files, the message is kept in their history.
+    /// Files are only moved along the transitions of the configured workflow
+    pub fn mark(
+        &mut self,
+        prog: Progress,
+        paths: &[PathBuf],
+        message: Option<&str>,
+    ) -> Result<Batch> {
+        let batch = Batch::run(&self.records.expand(&self.repo, paths)?, |path| {
+            let file = self.records.get(&self.repo, path)?;
+            if !self.repo.config.can_move(&file.progress, &prog) {
+                return Err(GitTransError::InvalidTransition {
+                    path: file.path,
+                    from: file.progress.to_string(),
+                    to: prog.to_string(),
+                });
+            }
+            self.records
+                .mark_progress(&self.repo, prog.clone(), path, message)
+        });
+        self.save()?;
+        Ok(batch)
+    }
+
+    /// Sync files with their latest revision, their diff files are removed
+    pub fn sync(&mut self, paths: &[PathBuf]) -> Result<Batch> {
+        let batch = Batch::run(&self.records.expand(&self.repo, paths)?, |path| {
+            let file = self.records.set_synced(&self.repo, path)?;
+            remove_diff_file_from_trans(&self.repo, path)?;
+            Ok(file)
+        });
+        self.save()?;
+        Ok(batch)
+    }
+
+    /// Sync files with their latest revision, merging the upstream changes into the
+    /// translations in the translation directory. Returns the number of conflicts of
+    /// each file, files with conflicts are marked to translate again
+    pub fn merge(&mut self, paths: &[PathBuf]) -> Result<Batch<(TrackedFile, usize)>> {
+        let root_dir = &self.repo.root_dir;
+        let batch = Batch::run(&self.records.expand(&self.repo, paths)?, |path| {
+            let file = self.records.get(&self.repo, path)?;
+            let upstream = get_file_at_rev(root_dir, &file.path, "HEAD")
+                .filter(|_| file.orphaned != Some(true))
+                .ok_or_else(|| GitTransError::Orphaned(file.path.clone()))?;
+            let base = get_file_at_rev(root_dir, &file.path, &file.track_rev)
+                .ok_or_else(|| GitTransError::InvalidRevision(file.track_rev.clone()))?;
+            let trans_path = self.repo.trans_dir().join(&file.path);
+            let translation = fs::read(&trans_path)?;
+            let merged = merge(
+                &file.path,
+                &String::from_utf8_lossy(&base),
+                &String::from_utf8_lossy(&translation),
+                &String::from_utf8_lossy(&upstream),
+            );
+            fs::write(&trans_path, merged.content)?;
+            let mut file = self.records.set_synced(&self.repo, path)?;
+            remove_diff_file_from_trans(&self.repo, path)?;
+            if merged.conflicts > 0 {
+                let conflicts = format!("{} merge conflicts", merged.conflicts);
+                file = self.records.mark_progress(
+                    &self.repo,
+                    self.repo.config.first_progress(),
+                    path,
+                    Some(&conflicts),
+                )?;
+            }
+            Ok((file, merged.conflicts))
+        });
+        self.save()?;
+        Ok(batch)
+    }
+
+    /// Assign a user to translate files, or to review them if `reviewer` is set
+    pub fn assign(
+        &mut self,
+        user: &str,
+        reviewer: bool,
+        paths: &[PathBuf],
+        message: Option<&str>,
+    ) -> Result<Batch> {
+        let batch = Batch::run(&self.records.expand(&self.repo, paths)?, |path| {
+            self.records
+                .assign(&self.repo, user, reviewer, path, message)
+        });
+        self.save()?;
+        Ok(batch)
+    }
+
+    /// Lock or unlock files
+    pub fn lock(
+        &mut self,
+        locked: bool,
+        paths: &[PathBuf],
+        message: Option<&str>,
+    ) -> Result<Batch> {
+        let batch = Batch::run(&self.records.expand(&self.repo, paths)?, |path| {
+            self.records.set_lock(&self.repo, locked, path, message)
+        });
+        self.save()?;
+        Ok(batch)
+    }
+
+    /// Update sync status for all files, returns the sources deleted or renamed upstream.
+    /// Renamed records and their copies are moved if `follow_renames` is set
+    pub fn update(&mut self, follow_renames: bool) -> Result<Vec<UpstreamChange>> {
+        self.records.update_sync(&self.repo, follow_renames)
+    }
+
+    /// Paths of all unsynced files that are not orphaned
+    pub fn unsynced_paths(&self) -> Vec<PathBuf> {
+        self.records
+            .with_synced(false)
+            .into_iter()
+            .filter(|file| file.orphaned != Some(true))
+            .map(|file| self.repo.root_dir.join(&file.path))
+            .collect()
+    }
+
+    /// Diff files with their latest revision
+    pub fn diff(
+        &self,
+        paths: &[PathBuf],
+        options: &DiffOptions,
+    ) -> Result<Batch<(TrackedFile, String)>> {
+        Ok(Batch::run(
+            &self.records.expand(&self.repo, paths)?,
+            |path| {
+                let file = self.records.get(&self.repo, path)?;
+                let diff_file = self.file_diff(&file, options)?;
+                Ok((file, diff_file))
+            },
+        ))
+    }
+
+    /// Write diff files of files with their latest revision into the translation directory,
+    /// existing diff files are overwritten
+    pub fn gendiff(&self, paths: &[PathBuf], options: &DiffOptions) -> Result<Batch> {
+        Ok(Batch::run(
+            &self.records.expand(&self.repo, paths)?,
+            |path| {
+                let file = self.records.get(&self.repo, path)?;
+                let diff_file = self.file_diff(&file, options)?;
+                write_diff_file_to_trans(&self.repo, path, &diff_file)?;
+                Ok(file)
+            },
+        ))
+    }
+
+    /// Diff a file with its latest revision, mapped to the translation
+    /// and rendered as the options set
+    fn file_diff(&self, file: &TrackedFile, options: &DiffOptions) -> Result<String> {
+        let mut diff_file = file.upstream_diff(&self.repo, options)?;
+        if let Some(line_map) = file.line_map(&self.repo).filter(|_| options.mapped) {
+            let name = self.repo.trans_dir_rel().join(&file.path);
+            diff_file = line_map.map_diff(&diff_file, &name.to_string_lossy());
+        }
+        if options.side_by_side {
+            diff_file = side_by_side(&diff_file, terminal_width());
+        }
+        Ok(diff_file)
+    }
+
+    /// Files in the translation directory of the selected language that are not recorded,
+    /// leaving out the records, the configuration, the generated diff files
+    /// and the directories of the other languages
+    fn unrecorded_translations(&self) -> Result<Vec<PathBuf>> {
+        let trans_dir = self.repo.trans_dir();
+        if !trans_dir.is_dir() {
+            return Ok(Vec::new());
+        }
+        // only the translation directory of the default language contains the others
+        let mut excluded: Vec<PathBuf> = Vec::new();
+        if self.repo.lang_dir.as_os_str().is_empty() {
+            excluded.push(PathBuf::from(&self.repo.config.records_file));
+            excluded.push(PathBuf::from(CONFIG_FILE));
+            excluded.extend(
+                self.records
+                    .langs()
+                    .iter()
+                    .map(|lang| self.records.lang_dir(lang))
+                    .filter(|dir| !dir.as_os_str().is_empty()),
+            );
+        }
+        excluded.extend(
+            self.records
+                .files
+                .iter()
+                .flat_map(|file| [file.path.clone(), get_diff_file(Path::new(""), &file.path)]),
+        );
+        Ok(list_files(&trans_dir)?
+            .into_iter()
+            .filter(|path| !excluded.iter().any(|excluded| path.starts_with(excluded)))
+            .collect())
+    }
+
+    /// Copy the translations of the recorded files into the root directory, returns the
+    /// covered paths relative to it. Orphaned files and files below the minimum progress
+    /// are left out, missing translations are skipped. With the `all` cover strategy the
+    /// files not recorded in the translation directory are copied too, unless a minimum
+    /// progress is given.
+    /// Refuses to cover when files not covered before have uncommitted changes, the covered
+    /// files are written to the cover manifest for `reset`. A dry run returns the files
+    /// whose content would change.
+    ///
+    /// With a worktree or output directory target, the tracked revision is checked out or
+    /// exported there first and the translations are copied on top of it, leaving the
+    /// working tree alone
+    pub fn cover(&self, options: &CoverOptions) -> Result<Batch<PathBuf>> {
+        let rev = options
+            .rev
+            .clone()
+            .unwrap_or_else(|| self.records.meta.track_rev.clone());
+        let root_dir = &self.repo.root_dir;
+        let trans_dir = self.repo.trans_dir();
+        let mut covered = Vec::new();
+        let target_dir = match &options.target {
+            CoverTarget::Root => {
+                covered = read_cover_manifest(root_dir)?;
+                let dirty: Vec<PathBuf> = list_modified_files(root_dir)?
+                    .into_iter()
+                    .filter(|path| !path.starts_with(&self.repo.config.trans_dir))
+                    .filter(|path| !covered.contains(path))
+                    .collect();
+                if !dirty.is_empty() {
+                    return Err(GitTransError::DirtyTree(dirty));
+                }
+                root_dir.clone()
+            }
+            CoverTarget::Worktree(dir) | CoverTarget::Out(dir) => {
+                // resolved in the repository, a reused worktree has a HEAD of its own
+                let Some(rev) = get_tag_rev(root_dir, &rev) else {
+                    return Err(GitTransError::InvalidRevision(rev));
+                };
+                let dir = std::path::absolute(dir)?;
+                match (options.dry_run, &options.target) {
+                    (true, _) => {}
+                    (false, CoverTarget::Worktree(_)) => {
+                        // a reused worktree has the files covered before reset, any other
+                        // local change is kept and refuses to switch it
+                        if dir.exists() {
+                            if !is_linked_worktree(root_dir, &dir)? {
+                                return Err(GitTransError::NotAWorktree(dir));
+                            }
+                            let covered = read_cover_manifest(&dir)?;
+                            let dirty: Vec<PathBuf> = list_modified_files(&dir)?
+                                .into_iter()
+                                .filter(|path| !covered.contains(path))
+                                .collect();
+                            if !dirty.is_empty() {
+                                return Err(GitTransError::DirtyTree(dirty));
+                            }
+                            reset_covered(&dir, false)?;
+                        }
+                        add_worktree(root_dir, &dir, &rev)?
+                    }
+                    (false, _) => export_tree(root_dir, &dir, &rev)?,
+                }
+                dir
+            }
+        };
+        // the current content of a file in the target, before covering
+        let current = |path: &Path| match options.target {
+            CoverTarget::Root => fs::read(root_dir.join(path)).ok(),
+            _ => get_file_at_rev(root_dir, path, &rev),
+        };
+        let (present, missing): (Vec<&TrackedFile>, Vec<&TrackedFile>) = self
+            .records
+            .files
+            .iter()
+            .filter(|file| file.orphaned != Some(true))
+            .filter(|file| match &options.min_progress {
+                // files in states unknown to the workflow are left out
+                Some(min) => {
+                    let config = &self.repo.config;
+                    config.rank(&file.progress) >= config.rank(min)
+                }
+                None => true,
+            })
+            .partition(|file| trans_dir.join(&file.path).is_file());
+        // the files not recorded have no progress to compare with a minimum progress
+        let unrecorded = match (&self.repo.config.cover, &options.min_progress) {
+            (CoverStrategy::All, None) => self.unrecorded_translations()?,
+            _ => Vec::new(),
+        };
+        let paths: Vec<PathBuf> = present
+            .iter()
+            .map(|file| file.path.clone())
+            .chain(unrecorded)
+            .filter(|path| {
+                // a dry run lists only the files that would change
+                !options.dry_run || fs::read(trans_dir.join(path)).ok() != current(path)
+            })
+            .collect();
+        let mut batch = Batch::run(&paths, |path| {
+            if !options.dry_run {
+                copy_file(trans_dir.join(path), target_dir.join(path), true)?;
+            }
+            Ok(path.to_path_buf())
+        });
+        batch.skipped = missing.into_iter().map(|file| file.path.clone()).collect();
+        // the files covered into the root directory or a worktree are reset later
+        if !options.dry_run && !matches!(options.target, CoverTarget::Out(_)) {
+            for path in batch.done.iter() {
+                if !covered.contains(path) {
+                    covered.push(path.clone());
+                }
+            }
+            write_cover_manifest(&target_dir, &covered)?;
+        }
+        Ok(batch)
+    }
+
+    /// Cover the translations into the root directory, run a command with the inherited stdio
+    /// and reset the covered files, even if the command fails or is interrupted by Ctrl-C
+    pub fn build(&self, command: &[String]) -> Result<()> {
+        let (program, args) = command
+            .split_first()
+            .ok_or_else(|| GitTransError::Config("no build command given".to_string()))?;
+        // Ctrl-C reaches the command in the same process group, git-trans waits for it to
+        // exit and resets. An interrupt while covering is recorded and the command skipped.
+        // Fails if a handler is already set, which is as good
+        let interrupted = Arc::new(AtomicBool::new(false));
+        let flag = Arc::clone(&interrupted);
+        let _ = ctrlc::set_handler(move || flag.store(true, Ordering::SeqCst));
+        let batch = self.cover(&CoverOptions::default())?;
+        let status = if !batch.failed.is_empty() {
+            Err(GitTransError::Partial {
+                failed: batch.failed.len(),
+                total: batch.total(),
+            })
+        } else if interrupted.load(Ordering::SeqCst) {
+            Err(GitTransError::Interrupted(command.join(" ")))
+        } else {
+            Command::new(program)
+                .args(args)
+                .current_dir(&self.repo.root_dir)
+                .status()
+                .map_err(GitTransError::from)
+        };
+        self.reset(false)?;
+        match status? {
+            status if status.success() => Ok(()),
+            status => Err(GitTransError::BuildFailed {
+                command: command.join(" "),
+                code: status.code(),
+            }),
+        }
+    }
+
+    /// Install a pre-commit hook running `git trans check-commit`, an existing hook
+    /// not installed by git-trans is only replaced with `force`. Returns the hook path
+    pub fn install_hooks(&self, force: bool) -> Result<PathBuf> {
+        let hook = get_git_path(&self.repo.root_dir, "hooks")?.join("pre-commit");
+        if hook.is_file() && !force && !fs::read_to_string(&hook)?.contains(HOOK_MARKER) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!(
+                    "{} already exists, use --force to replace it",
+                    hook.display()
+                ),
+            )
+            .into());
+        }
+        if let Some(hooks_dir) = hook.parent() {
+            fs::create_dir_all(hooks_dir)?;
+        }
+        fs::write(
+            &hook,
+            format!("#!/bin/sh\n{HOOK_MARKER}\nexec git trans check-commit\n"),
+        )?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&hook, fs::Permissions::from_mode(0o755))?;
+        }
+        Ok(hook)
+    }
+
+    /// Remove the pre-commit hook installed by git-trans, returns the hook path if removed
+    pub fn uninstall_hooks(&self) -> Result<Option<PathBuf>> {
+        let hook = get_git_path(&self.repo.root_dir, "hooks")?.join("pre-commit");
+        if hook.is_file() && fs::read_to_string(&hook)?.contains(HOOK_MARKER) {
+            fs::remove_file(&hook)?;
+            return Ok(Some(hook));
+        }
+        Ok(None)
+    }
+
+    /// Check the changes staged for the next commit of the current directory, returns the
+    /// problems found: changes outside the translation directory, a staged records file
+    /// that does not parse, and records whose translation is not in the index
+    pub fn check_commit() -> Result<Vec<String>> {
+        Project::check_commit_at(Path::new("."))
+    }
+
+    /// Check the changes staged for the next commit of the repository containing a directory,
+    /// like [`Project::check_commit`]
+    pub fn check_commit_at(dir: &Path) -> Result<Vec<String>> {
+        let repo = Repo::open(dir)?;
+        let root_dir = &repo.root_dir;
+        let trans_dir = &repo.config.trans_dir;
+        let mut problems: Vec<String> = list_staged_files(root_dir)?
+            .into_iter()
+            .filter(|path| !path.starts_with(trans_dir))
+            .map(|path| format!("staged outside {trans_dir}: {}", path.display()))
+            .collect();
+        match Records::load_staged(&repo) {
+            Ok(records) => {
+                let tracked = list_tracked_files(root_dir, Path::new(trans_dir))?;
+                for records in records.each_lang() {
+                    let lang_dir = repo.for_records(&records).trans_dir_rel();
+                    for file in records.files.iter() {
+                        let trans_file = lang_dir.join(&file.path);
+                        if !tracked.contains(&trans_file) {
+                            problems.push(format!("translation missing: {}", trans_file.display()));
+                        }
+                    }
+                }
+            }
+            Err(err) => problems.push(err.to_string()),
+        }
+        Ok(problems)
+    }
+
+    /// Reset the files covered into the root directory to the latest revision,
+    /// returns the files reset. A dry run only returns them
+    pub fn reset(&self, dry_run: bool) -> Result<Vec<PathBuf>> {
+        reset_covered(&self.repo.root_dir, dry_run)
+    }
+
+    /// Get logs of the translation directory
+    pub fn log(&self) -> Result<String> {
+        get_log(&self.repo.root_dir, &self.repo.trans_dir())
+    }
+}
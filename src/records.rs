@@ -1,34 +1,81 @@
-use chrono::Local;
-use clap::{Subcommand, ValueEnum};
+use chrono::{Local, SecondsFormat};
 use colored::*;
-use core::{option::Option::None, result::Result};
+use core::option::Option::None;
 // use log::debug;
 use serde::{Deserialize, Serialize};
 use std::{
+    collections::{BTreeMap, HashSet},
     fmt::Display,
     fs,
-    io::{Error, ErrorKind},
     path::Path,
     path::PathBuf,
     str::FromStr,
 };
 use toml::value::Datetime;
 
-use crate::{git::*, utils::*};
+use crate::{
+    config::Config,
+    diff::{DiffOptions, unwrap_paragraphs},
+    errors::{GitTransError, Result},
+    git::*,
+    project::Repo,
+    segment::{AlignedSegment, LineMap, OutdatedSegment, align, outdated},
+    stats::Stats,
+    utils::*,
+};
 
 /// Records file structure
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Records {
     pub meta: Meta,
+    /// Files of the selected language, the default language in the records file
     pub files: Vec<TrackedFile>,
+    /// Files of the other languages by language code, kept in `.trans/<lang>/`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub langs: BTreeMap<String, LangFiles>,
+    /// Selected language, `None` for the default language
+    #[serde(skip)]
+    selected: Option<String>,
+}
+
+/// Files recorded for a language other than the default one
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LangFiles {
+    #[serde(default)]
+    pub files: Vec<TrackedFile>,
+}
+
+/// Number of files of a language by progress and status
+#[derive(Debug, Clone)]
+pub struct Summary {
+    pub lang: String,
+    pub files: usize,
+    /// Number of files in each state of the workflow
+    pub progress: Vec<(Progress, usize)>,
+    pub unsynced: usize,
+    pub orphaned: usize,
+}
+
+impl Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {} files", self.lang.bold(), self.files)?;
+        for (prog, count) in self.progress.iter() {
+            write!(f, ", {count} {}", prog.0.to_lowercase())?;
+        }
+        write!(
+            f,
+            ", {} unsynced, {} orphaned",
+            self.unsynced, self.orphaned
+        )
+    }
 }
 
 /// Records file meta information
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Meta {
     /// Project name
     pub project_name: String,
-    /// Language code, like zh-CN, en-US, etc.
+    /// Language code of the default language, like zh-CN, en-US, etc.
     pub lang: String,
     /// track repo revision, hash or tag
     pub track_rev: String,
@@ -36,51 +83,288 @@ pub struct Meta {
     pub datetime: Datetime,
 }
 
-/// File status
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Subcommand, ValueEnum)]
-pub enum Progress {
-    /// File is to be translated
-    Trans,
-    /// File is to be reviewed
-    Review,
-    /// File is done
-    Done,
-}
+/// File status, the name of a state of the configured workflow,
+/// `Trans`, `Review` or `Done` by default
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+#[serde(transparent)]
+pub struct Progress(pub String);
+
+impl Progress {
+    /// Progress of a state name
+    pub fn new(name: &str) -> Progress {
+        Progress(name.to_string())
+    }
 
-impl FromStr for Progress {
-    type Err = ();
+    /// Name of the state in its configured colour
+    pub fn colored(&self, config: &Config) -> ColoredString {
+        self.paint(config, &self.0)
+    }
+
+    /// Letter of the state in its configured colour, for status columns
+    pub fn letter(&self, config: &Config) -> ColoredString {
+        self.paint(config, &config.letter(self).to_string())
+    }
 
-    fn from_str(input: &str) -> Result<Progress, Self::Err> {
-        match input {
-            "Trans" => Ok(Progress::Trans),
-            "Review" => Ok(Progress::Review),
-            "Done" => Ok(Progress::Done),
-            _ => Err(()),
+    /// Paint a text in the configured colour of the state
+    fn paint(&self, config: &Config, text: &str) -> ColoredString {
+        match config
+            .workflow_state(self)
+            .and_then(|state| state.color.as_deref())
+        {
+            Some(color) => text.color(color),
+            None => text.normal(),
         }
     }
+
+    /// Check if files in the state wait for their reviewer
+    pub fn is_review(&self, config: &Config) -> bool {
+        config
+            .workflow_state(self)
+            .is_some_and(|state| state.review)
+    }
 }
 
 impl Display for Progress {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:?}", self)
+        write!(f, "{}", self.0)
+    }
+}
+
+/// State of the copy in the .trans directory compared with the source at the track revision
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CopyState {
+    /// The copy does not exist
+    Missing,
+    /// The source cannot be read at the track revision
+    Unknown,
+    /// The copy is the same as the source, not translated yet
+    Same,
+    /// The copy differs from the source
+    Differs,
+}
+
+/// Change of a tracked source file upstream, found by updating the records
+#[derive(Debug, Clone, PartialEq)]
+pub enum UpstreamChange {
+    /// The source is moved to a new path, `followed` if the record and the copy are moved too
+    Renamed {
+        from: PathBuf,
+        to: PathBuf,
+        followed: bool,
+    },
+    /// The source is deleted, the record is orphaned
+    Deleted(PathBuf),
+}
+
+impl Display for CopyState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", format!("{:?}", self).to_lowercase())
     }
 }
 
+/// Everything known about a tracked file
+#[derive(Debug, Serialize, Clone)]
+pub struct FileInfo {
+    pub path: PathBuf,
+    pub track_rev: String,
+    /// Commit date of the track revision
+    pub track_date: Option<String>,
+    /// Commit subject of the track revision
+    pub track_subject: Option<String>,
+    /// Latest revision of the source, `None` if it is missing upstream
+    pub upstream_rev: Option<String>,
+    /// Number of upstream commits since the track revision
+    pub commits_since: usize,
+    pub progress: Progress,
+    pub locked: bool,
+    pub synced: bool,
+    pub copy: CopyState,
+    /// Blob hash of the source content at the track revision
+    pub source_blob: Option<String>,
+    /// Blob hash of the source content at HEAD, `None` if it is missing upstream
+    pub upstream_blob: Option<String>,
+    /// The source is deleted or renamed upstream
+    pub orphaned: bool,
+    /// Number of translated segments outdated by the latest source, `None` if not aligned
+    pub outdated_segments: Option<usize>,
+    /// Translator of the file
+    pub assignee: Option<String>,
+    /// Reviewer of the file
+    pub reviewer: Option<String>,
+    /// Who marked the file for review last
+    pub review_requester: Option<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct TrackedFile {
     pub path: PathBuf,
     pub track_rev: String,
+    /// Blob hash of the source content at the track revision
+    pub source_blob: Option<String>,
     pub progress: Progress,
     pub synced: bool,
     pub locked: Option<bool>,
+    /// The source is deleted or renamed upstream
+    pub orphaned: Option<bool>,
+    /// Source segments at the track revision aligned with the translation
+    pub segments: Option<Vec<AlignedSegment>>,
+    /// Translator of the file
+    pub assignee: Option<String>,
+    /// Reviewer of the file
+    pub reviewer: Option<String>,
+    /// Who marked the file for review last
+    pub review_requester: Option<String>,
+    /// Transitions of the file, oldest first
+    pub history: Option<Vec<HistoryEntry>>,
+}
+
+/// A transition of a tracked file, like a progress change, lock, sync or assignment
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct HistoryEntry {
+    /// Local datetime, rfc3339 format
+    pub datetime: String,
+    /// Email of the git user who made the transition
+    pub author: Option<String>,
+    /// The transition, like `mark review`, `lock` or `sync <rev>`
+    pub action: String,
+    pub message: Option<String>,
+}
+
+impl TrackedFile {
+    /// Append a transition to the history, made by the git user now
+    pub fn record_history(&mut self, repo: &Repo, action: String, message: Option<&str>) {
+        let entry = HistoryEntry {
+            datetime: Local::now().to_rfc3339_opts(SecondsFormat::Secs, false),
+            author: get_user_email(&repo.root_dir),
+            action,
+            message: message.map(str::to_string),
+        };
+        self.history.get_or_insert_with(Vec::new).push(entry);
+    }
+
+    /// Check if the file is for a user to work on, assigned to translate or to review
+    pub fn is_mine(&self, config: &Config, user: &str) -> bool {
+        self.assignee.as_deref() == Some(user)
+            || self.progress.is_review(config) && self.reviewer.as_deref() == Some(user)
+    }
+
+    /// Collect all information known about the file
+    pub fn info(&self, repo: &Repo) -> Result<FileInfo> {
+        let root_dir = &repo.root_dir;
+        let (track_date, track_subject) = get_rev_info(root_dir, &self.track_rev).unzip();
+        let upstream_blob = get_blob(root_dir, &self.path, "HEAD");
+        let upstream_rev = Some(get_file_rev(root_dir, &self.path)?).filter(|rev| !rev.is_empty());
+        let commits_since = match upstream_rev {
+            Some(_) => count_commits_since(root_dir, &self.path, &self.track_rev)?,
+            None => 0,
+        };
+        let trans_content = fs::read(repo.trans_dir().join(&self.path)).ok();
+        let source_content = get_file_at_rev(root_dir, &self.path, &self.track_rev);
+        let copy = match (trans_content, source_content) {
+            (None, _) => CopyState::Missing,
+            (_, None) => CopyState::Unknown,
+            (Some(trans), Some(source)) if trans == source => CopyState::Same,
+            _ => CopyState::Differs,
+        };
+        Ok(FileInfo {
+            path: self.path.clone(),
+            track_rev: self.track_rev.clone(),
+            track_date,
+            track_subject,
+            upstream_rev,
+            commits_since,
+            progress: self.progress.clone(),
+            locked: self.locked == Some(true),
+            synced: self.synced,
+            copy,
+            source_blob: self.source_blob.clone(),
+            upstream_blob,
+            orphaned: self.orphaned == Some(true),
+            outdated_segments: self.outdated_segments(repo).map(|outdated| outdated.len()),
+            assignee: self.assignee.clone(),
+            reviewer: self.reviewer.clone(),
+            review_requester: self.review_requester.clone(),
+        })
+    }
+
+    /// Align the segments of the source at the track revision with the translation
+    pub fn align(&mut self, repo: &Repo) {
+        let source = get_file_at_rev(&repo.root_dir, &self.path, &self.track_rev);
+        let translation = fs::read(repo.trans_dir().join(&self.path)).ok();
+        self.segments = source.zip(translation).map(|(source, translation)| {
+            align(
+                &self.path,
+                &String::from_utf8_lossy(&source),
+                &String::from_utf8_lossy(&translation),
+            )
+        });
+    }
+
+    /// Map lines of the source at the track revision to the translation,
+    /// `None` if either of them cannot be read
+    pub fn line_map(&self, repo: &Repo) -> Option<LineMap> {
+        let source = get_file_at_rev(&repo.root_dir, &self.path, &self.track_rev)?;
+        let translation = fs::read(repo.trans_dir().join(&self.path)).ok()?;
+        Some(LineMap::new(
+            &self.path,
+            &String::from_utf8_lossy(&source),
+            &String::from_utf8_lossy(&translation),
+        ))
+    }
+
+    /// Find the translated segments outdated by the latest source, `None` if not aligned
+    /// or the source is missing upstream
+    pub fn outdated_segments(&self, repo: &Repo) -> Option<Vec<OutdatedSegment>> {
+        let upstream = get_file_at_rev(&repo.root_dir, &self.path, "HEAD")?;
+        Some(outdated(
+            &self.path,
+            self.segments.as_ref()?,
+            &String::from_utf8_lossy(&upstream),
+        ))
+    }
+
+    /// Get diff of the source from the track revision to the latest content,
+    /// using the source blob when it is recorded and still in the repository
+    pub fn upstream_diff(&self, repo: &Repo, options: &DiffOptions) -> Result<String> {
+        let root_dir = &repo.root_dir;
+        let args = options.git_args();
+        if options.ignore_rewrap {
+            // diff the sources with the paragraphs unwrapped
+            let unwrapped = |content: Vec<u8>| {
+                unwrap_paragraphs(&self.path, &String::from_utf8_lossy(&content))
+            };
+            let old = get_file_at_rev(root_dir, &self.path, &self.track_rev)
+                .ok_or_else(|| GitTransError::InvalidRevision(self.track_rev.clone()))?;
+            let new = get_file_at_rev(root_dir, &self.path, "HEAD")
+                .ok_or_else(|| GitTransError::Orphaned(self.path.clone()))?;
+            return get_content_diff(
+                &self.path,
+                unwrapped(old).as_bytes(),
+                unwrapped(new).as_bytes(),
+                &args,
+            );
+        }
+        if let (Some(old_blob), Some(new_blob)) =
+            (&self.source_blob, get_blob(root_dir, &self.path, "HEAD"))
+            && object_exists(root_dir, old_blob)
+        {
+            return get_blob_diff(root_dir, &self.path, old_blob, &new_blob, &args);
+        }
+        let upstream_rev = get_file_rev(root_dir, &self.path)?;
+        get_diff(root_dir, &self.path, &self.track_rev, &upstream_rev, &args)
+    }
 }
 
 impl Records {
     /// initial records.toml
-    pub fn init(lang: &str, tag: &String) -> Result<Records, Error> {
-        let root_dir: PathBuf = get_root_dir().unwrap();
-        let project_name = root_dir.file_name().unwrap().to_str().unwrap().to_string();
-        if let Some(rev) = get_tag_rev(tag) {
+    pub fn init(repo: &Repo, lang: &str, tag: &String) -> Result<Records> {
+        let project_name = repo
+            .root_dir
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        if let Some(rev) = get_tag_rev(&repo.root_dir, tag) {
             Ok(Records {
                 meta: Meta {
                     project_name,
@@ -89,39 +373,145 @@ impl Records {
                     datetime: Datetime::from_str(&Local::now().to_rfc3339()).unwrap(),
                 },
                 files: Vec::new(),
+                langs: BTreeMap::new(),
+                selected: None,
             })
         } else {
-            let err = Error::new(
-                ErrorKind::InvalidInput,
-                format!("{tag} is not a valid revision"),
-            );
-            Err(err)
+            Err(GitTransError::InvalidRevision(tag.clone()))
         }
     }
+
+    /// Language of the selected files
+    pub fn lang(&self) -> &str {
+        self.selected.as_deref().unwrap_or(&self.meta.lang)
+    }
+
+    /// All recorded languages, the default one first
+    pub fn langs(&self) -> Vec<String> {
+        let mut langs = vec![self.meta.lang.clone()];
+        langs.extend(self.langs.keys().cloned());
+        langs
+    }
+
+    /// Directory of a language in the translation directory, empty for the default language
+    pub fn lang_dir(&self, lang: &str) -> PathBuf {
+        if lang == self.meta.lang {
+            PathBuf::new()
+        } else {
+            PathBuf::from(lang)
+        }
+    }
+
+    /// Add a language with no files
+    pub fn add_lang(&mut self, lang: &str) -> Result<()> {
+        if self.meta.lang == lang || self.langs.contains_key(lang) {
+            return Err(GitTransError::LangExists(lang.to_string()));
+        }
+        self.langs.insert(lang.to_string(), LangFiles::default());
+        Ok(())
+    }
+
+    /// Select the files of a language, they are swapped with the files of the
+    /// default language in `files` and swapped back when saving
+    pub fn select(&mut self, lang: &str) -> Result<()> {
+        if lang == self.lang() {
+            return Ok(());
+        }
+        if lang != self.meta.lang && !self.langs.contains_key(lang) {
+            return Err(GitTransError::UnknownLang(lang.to_string()));
+        }
+        if let Some(selected) = self.selected.take() {
+            let other = self
+                .langs
+                .get_mut(&selected)
+                .expect("selected language is recorded");
+            std::mem::swap(&mut self.files, &mut other.files);
+        }
+        if let Some(other) = self.langs.get_mut(lang) {
+            std::mem::swap(&mut self.files, &mut other.files);
+            self.selected = Some(lang.to_string());
+        }
+        Ok(())
+    }
+
+    /// Records of each language with its files selected, the default language first
+    pub fn each_lang(&self) -> Vec<Records> {
+        self.langs()
+            .iter()
+            .map(|lang| {
+                let mut records = self.clone();
+                records
+                    .select(lang)
+                    .expect("recorded languages can be selected");
+                records
+            })
+            .collect()
+    }
+
+    /// Count the selected files by progress and status
+    pub fn summary(&self, config: &Config) -> Summary {
+        let count =
+            |pred: &dyn Fn(&TrackedFile) -> bool| self.files.iter().filter(|f| pred(f)).count();
+        Summary {
+            lang: self.lang().to_string(),
+            files: self.files.len(),
+            progress: config
+                .workflow
+                .iter()
+                .map(|state| {
+                    let prog = Progress::new(&state.name);
+                    let files = count(&|file| file.progress == prog);
+                    (prog, files)
+                })
+                .collect(),
+            unsynced: count(&|file| !file.synced),
+            orphaned: count(&|file| file.orphaned == Some(true)),
+        }
+    }
+
+    /// Collect the progress statistics of the selected files,
+    /// grouping the directories by their leading `depth` components
+    pub fn stats(&self, repo: &Repo, depth: usize) -> Result<Stats> {
+        Stats::collect(repo, self.lang(), &self.files, depth)
+    }
+
     /// Add file to records
-    pub fn add(&mut self, path: &Path, lock: bool) -> Result<TrackedFile, Error> {
-        let path = unify(path);
-        if self.contains(&path) {
-            let err = Error::new(ErrorKind::AlreadyExists, "record already exists");
-            return Err(err);
+    pub fn add(&mut self, repo: &Repo, path: &Path, lock: bool) -> Result<TrackedFile> {
+        let root_dir = &repo.root_dir;
+        let path_rel_to_root = get_path_rel_to_root(root_dir, path)?;
+        if self.contains(repo, path) {
+            return Err(GitTransError::RecordExists(path_rel_to_root));
+        }
+
+        let track_rev = get_file_rev(root_dir, &path_rel_to_root)?;
+        if track_rev.is_empty() {
+            return Err(GitTransError::NotTracked(path_rel_to_root));
         }
 
-        let path_rel_to_root = get_path_rel_to_root(&path);
+        // the translation starts as a copy of the source
+        let source = get_file_at_rev(root_dir, &path_rel_to_root, &track_rev)
+            .map(|source| String::from_utf8_lossy(&source).to_string());
         let file = TrackedFile {
+            segments: source.map(|source| align(&path_rel_to_root, &source, &source)),
+            source_blob: get_blob(root_dir, &path_rel_to_root, &track_rev),
             path: path_rel_to_root,
-            track_rev: get_file_rev(&path),
-            progress: Progress::Trans,
+            track_rev,
+            progress: repo.config.initial_progress(),
             synced: true,
             locked: if lock { Some(true) } else { None },
+            orphaned: None,
+            assignee: None,
+            reviewer: None,
+            review_requester: None,
+            history: None,
         };
         self.files.push(file.clone());
         Ok(file)
     }
 
     /// Remove file from records
-    pub fn remove(&mut self, path: &Path) -> Result<TrackedFile, Error> {
-        let path = unify(path);
-        let path_rel_to_root = get_path_rel_to_root(&path);
+    pub fn remove(&mut self, repo: &Repo, path: &Path) -> Result<TrackedFile> {
+        let path_rel_to_root = get_path_rel_to_root(&repo.root_dir, path)?;
 
         if let Some(pos) = self
             .files
@@ -131,18 +521,16 @@ impl Records {
             let removed_record = self.files.remove(pos);
             Ok(removed_record)
         } else {
-            let err = Error::new(ErrorKind::NotFound, "record not found");
-            Err(err)
+            Err(GitTransError::RecordNotFound(path_rel_to_root))
         }
     }
 
     /// Update file in records
-    pub fn update<F>(&mut self, path: &Path, modify_fn: F) -> Result<TrackedFile, Error>
+    pub fn update<F>(&mut self, repo: &Repo, path: &Path, modify_fn: F) -> Result<TrackedFile>
     where
         F: FnOnce(&mut TrackedFile),
     {
-        let path = unify(path);
-        let path_rel_to_root = get_path_rel_to_root(&path);
+        let path_rel_to_root = get_path_rel_to_root(&repo.root_dir, path)?;
 
         if let Some(file) = self
             .files
@@ -150,167 +538,331 @@ impl Records {
             .find(|file| file.path == path_rel_to_root)
         {
             modify_fn(file);
-            let file_result = file.clone();
-            self.save()?;
-            Ok(file_result)
+            Ok(file.clone())
         } else {
-            let err = Error::new(ErrorKind::NotFound, "record not found");
-            Err(err)
+            Err(GitTransError::RecordNotFound(path_rel_to_root))
         }
     }
 
     /// Get file in records
-    pub fn get(&mut self, path: &Path) -> Result<TrackedFile, Error> {
-        let noop = |_: &mut TrackedFile| ();
-        self.update(path, noop)
-    }
-
-    /// Save records to records.toml
-    pub fn save(&self) -> std::io::Result<()> {
-        let toml = toml::to_string(self).unwrap();
-        fs::write(get_records_toml(), toml)
+    pub fn get(&self, repo: &Repo, path: &Path) -> Result<TrackedFile> {
+        let path_rel_to_root = get_path_rel_to_root(&repo.root_dir, path)?;
+        self.files
+            .iter()
+            .find(|file| file.path == path_rel_to_root)
+            .cloned()
+            .ok_or(GitTransError::RecordNotFound(path_rel_to_root))
     }
 
-    /// Show all files in records
-    pub fn show_all(&self) {
-        if self.files.is_empty() {
-            println!("No files in records.");
-            return;
-        }
-        println!(
-            "{}, {}, {} | {} | {}",
-            "T: Trans".red(),
-            "R: Review".yellow(),
-            "D: Done".green(),
-            "S: Synced".bright_blue(),
-            "L: Locked".bright_green()
-        );
-        for file in self.files.iter() {
-            let prog = match file.progress {
-                Progress::Trans => "T".red(),
-                Progress::Review => "R".yellow(),
-                Progress::Done => "D".green(),
-            };
-            let synced = if file.synced {
-                "S".bright_blue()
-            } else {
-                "-".truecolor(128, 128, 128)
-            };
-            let lock = if file.locked == Some(true) {
-                "L".bright_green()
-            } else {
-                "-".truecolor(128, 128, 128)
-            };
-            println!("{prog}{synced}{lock}\t{}", file.path.display());
+    /// Load records from records.toml
+    pub fn load(repo: &Repo) -> Result<Records> {
+        let records_toml = repo.records_file();
+        if !records_toml.is_file() {
+            return Err(GitTransError::NotInitialized);
         }
+        let records_str = fs::read_to_string(&records_toml)?;
+        toml::from_str(&records_str).map_err(|source| GitTransError::RecordsParse {
+            path: records_toml,
+            source,
+        })
     }
 
-    /// Show files with specific status
-    pub fn show_progress(&self, prog: Progress) {
-        let files = self.files.iter().filter(|file| file.progress == prog);
-        if files.clone().count() == 0 {
-            println!("\nNo files are in the {:?} status.", prog);
-            return;
-        }
-        let prog = match prog {
-            Progress::Trans => "Trans".red(),
-            Progress::Review => "Review".yellow(),
-            Progress::Done => "Done".green(),
-        };
-        for file in files {
-            println!("{}\t{}", prog, file.path.display());
-        }
+    /// Load records from the records.toml staged for the next commit
+    pub fn load_staged(repo: &Repo) -> Result<Records> {
+        let records_toml = repo.records_file();
+        let path = get_path_rel_to_root(&repo.root_dir, &records_toml)?;
+        // an empty revision reads the file from the index
+        let records =
+            get_file_at_rev(&repo.root_dir, &path, "").ok_or(GitTransError::NotInitialized)?;
+        toml::from_str(&String::from_utf8_lossy(&records)).map_err(|source| {
+            GitTransError::RecordsParse {
+                path: records_toml,
+                source,
+            }
+        })
     }
 
-    pub fn show_synced(&self, synced: bool) {
-        let files = self.files.iter().filter(|file| file.synced == synced);
-        if files.clone().count() == 0 {
-            println!(
-                "\nNo files are {}.",
-                if synced { "Synced" } else { "Unsynced" }
-            );
-            return;
-        }
-        for file in files {
-            println!(
-                "{}\t{}",
-                if file.synced {
-                    "Synced".bright_blue()
-                } else {
-                    "Unsynced".blue()
-                },
-                file.path.display()
-            );
+    /// Save records to records.toml
+    pub fn save(&self, repo: &Repo) -> Result<()> {
+        let toml = match self.selected {
+            Some(_) => {
+                // the files of the default language are stored in `files`
+                let mut records = self.clone();
+                records.select(&self.meta.lang)?;
+                toml::to_string(&records)
+            }
+            None => toml::to_string(self),
         }
+        .expect("records are always serializable");
+        fs::write(repo.records_file(), toml)?;
+        Ok(())
     }
 
-    pub fn show_locked(&self, locked: bool) {
-        let files = self.files.iter().filter(|file| {
-            if locked {
-                file.locked == Some(true)
-            } else {
-                file.locked.is_none()
+    /// Files recorded under a directory relative to the root directory, and the first
+    /// level subdirectories containing recorded files if not listed recursively
+    pub fn in_dir(&self, dir: &Path, recursive: bool) -> (Vec<&TrackedFile>, Vec<PathBuf>) {
+        let dir = unify(dir);
+        let mut files = Vec::new();
+        let mut subdirs: Vec<PathBuf> = Vec::new();
+        for file in self.files.iter().filter(|file| file.path.starts_with(&dir)) {
+            let parent = file.path.parent().unwrap_or(Path::new(""));
+            if recursive || parent == dir {
+                files.push(file);
+            } else if let Some(name) = file
+                .path
+                .strip_prefix(&dir)
+                .ok()
+                .and_then(|p| p.iter().next())
+            {
+                // only list the first level of subdirectories
+                let subdir = dir.join(name);
+                if !subdirs.contains(&subdir) {
+                    subdirs.push(subdir);
+                }
             }
-        });
-        if files.clone().count() == 0 {
-            println!(
-                "\nNo files are {}.",
-                if locked { "Locked" } else { "Unlocked" }
-            );
-            return;
-        }
-        for file in files {
-            println!(
-                "{}\t{}",
-                if locked {
-                    "Locked".bright_green()
-                } else {
-                    "Unlocked".green()
-                },
-                file.path.display()
-            );
         }
+        (files, subdirs)
+    }
+
+    /// Files with specific progress
+    pub fn with_progress(&self, prog: &Progress) -> Vec<&TrackedFile> {
+        self.files
+            .iter()
+            .filter(|file| &file.progress == prog)
+            .collect()
+    }
+
+    /// Files synced or unsynced
+    pub fn with_synced(&self, synced: bool) -> Vec<&TrackedFile> {
+        self.files
+            .iter()
+            .filter(|file| file.synced == synced)
+            .collect()
+    }
+
+    /// Files in states unknown to the workflow
+    pub fn with_unknown_progress(&self, config: &Config) -> Vec<&TrackedFile> {
+        self.files
+            .iter()
+            .filter(|file| config.workflow_state(&file.progress).is_none())
+            .collect()
+    }
+
+    /// Files deleted or renamed upstream
+    pub fn with_orphaned(&self) -> Vec<&TrackedFile> {
+        self.files
+            .iter()
+            .filter(|file| file.orphaned == Some(true))
+            .collect()
+    }
+
+    /// Files locked or unlocked
+    pub fn with_locked(&self, locked: bool) -> Vec<&TrackedFile> {
+        self.files
+            .iter()
+            .filter(|file| (file.locked == Some(true)) == locked)
+            .collect()
     }
 
     /// Mark file status in records
-    pub fn mark_progress(&mut self, prog: Progress, path: &Path) -> Result<TrackedFile, Error> {
-        let mark_prog = |file: &mut TrackedFile| file.progress = prog;
-        self.update(path, mark_prog)
+    /// Marking a file for review records the git user as the requester
+    pub fn mark_progress(
+        &mut self,
+        repo: &Repo,
+        prog: Progress,
+        path: &Path,
+        message: Option<&str>,
+    ) -> Result<TrackedFile> {
+        let review = prog.is_review(&repo.config);
+        let requester = review.then(|| get_user_email(&repo.root_dir)).flatten();
+        let mark_prog = |file: &mut TrackedFile| {
+            if review {
+                file.review_requester = requester;
+            }
+            let action = format!("mark {}", prog.to_string().to_lowercase());
+            file.record_history(repo, action, message);
+            file.progress = prog;
+        };
+        self.update(repo, path, mark_prog)
+    }
+
+    /// Assign a user to translate a file, or to review it if `reviewer` is set
+    pub fn assign(
+        &mut self,
+        repo: &Repo,
+        user: &str,
+        reviewer: bool,
+        path: &Path,
+        message: Option<&str>,
+    ) -> Result<TrackedFile> {
+        let assign = |file: &mut TrackedFile| {
+            if reviewer {
+                file.reviewer = Some(user.to_string());
+                file.record_history(repo, format!("reviewer {user}"), message);
+            } else {
+                file.assignee = Some(user.to_string());
+                file.record_history(repo, format!("assign {user}"), message);
+            }
+        };
+        self.update(repo, path, assign)
     }
 
     /// Sync file revision in records
-    pub fn set_synced(&mut self, path: &Path) -> Result<TrackedFile, Error> {
+    pub fn set_synced(&mut self, repo: &Repo, path: &Path) -> Result<TrackedFile> {
+        let file = self.get(repo, path)?;
+        if file.orphaned == Some(true) {
+            return Err(GitTransError::Orphaned(file.path));
+        }
+        let track_rev = get_file_rev(&repo.root_dir, &file.path)?;
+        let source_blob = get_blob(&repo.root_dir, &file.path, &track_rev);
         let sync = |file: &mut TrackedFile| {
-            file.track_rev = get_file_rev(path);
+            file.record_history(repo, format!("sync {track_rev}"), None);
+            file.track_rev = track_rev;
+            file.source_blob = source_blob;
             file.synced = true;
+            file.align(repo);
         };
-        self.update(path, sync)
+        self.update(repo, path, sync)
     }
 
-    /// Update sync status for all files
-    pub fn update_sync(&mut self) {
+    /// Update sync status for all files, and find the sources deleted or renamed upstream.
+    /// Renamed records and their copies are moved to the new path if `follow_renames` is set,
+    /// otherwise they are orphaned like the deleted ones
+    pub fn update_sync(
+        &mut self,
+        repo: &Repo,
+        follow_renames: bool,
+    ) -> Result<Vec<UpstreamChange>> {
+        let root_dir = &repo.root_dir;
+        let trans_dir = repo.trans_dir();
+        let mut recorded: HashSet<PathBuf> =
+            self.files.iter().map(|file| file.path.clone()).collect();
+        let mut changes = Vec::new();
         for file in self.files.iter_mut() {
-            file.synced = file.track_rev == get_file_rev(&file.path);
+            // records written before source blobs were tracked get them from the track revision
+            if file.source_blob.is_none() {
+                file.source_blob = get_blob(root_dir, &file.path, &file.track_rev);
+            }
+            let mut upstream_blob = get_blob(root_dir, &file.path, "HEAD");
+            if upstream_blob.is_none() {
+                let renamed = get_renamed_path(
+                    root_dir,
+                    &file.path,
+                    &file.track_rev,
+                    &repo.config.trans_dir,
+                );
+                let change = match renamed {
+                    // a recorded path or copy is never overwritten by a rename
+                    Some(to)
+                        if follow_renames
+                            && !recorded.contains(&to)
+                            && move_in_trans(&trans_dir, &file.path, &to).is_ok() =>
+                    {
+                        recorded.insert(to.clone());
+                        file.record_history(repo, format!("rename {}", to.display()), None);
+                        // the track revision must have the file at its new path
+                        file.track_rev = get_file_rev(root_dir, &to)?;
+                        let from = std::mem::replace(&mut file.path, to.clone());
+                        upstream_blob = get_blob(root_dir, &file.path, "HEAD");
+                        UpstreamChange::Renamed {
+                            from,
+                            to,
+                            followed: true,
+                        }
+                    }
+                    Some(to) => UpstreamChange::Renamed {
+                        from: file.path.clone(),
+                        to,
+                        followed: false,
+                    },
+                    None => UpstreamChange::Deleted(file.path.clone()),
+                };
+                let followed = matches!(change, UpstreamChange::Renamed { followed: true, .. });
+                changes.push(change);
+                if !followed {
+                    if file.orphaned != Some(true) {
+                        file.record_history(repo, "orphan".to_string(), None);
+                    }
+                    file.orphaned = Some(true);
+                    file.synced = false;
+                    continue;
+                }
+            }
+            file.orphaned = None;
+            file.align(repo);
+            // compare content when possible, so rewritten history is not reported as changes
+            let synced = match (&file.source_blob, upstream_blob) {
+                (Some(source_blob), Some(upstream_blob)) => *source_blob == upstream_blob,
+                _ => file.track_rev == get_file_rev(root_dir, &file.path)?,
+            };
+            if file.synced && !synced {
+                file.record_history(repo, "unsync".to_string(), None);
+            }
+            file.synced = synced;
         }
-        self.save().unwrap();
+        self.save(repo)?;
+        Ok(changes)
     }
 
     /// Lock file in records
-    pub fn set_lock(&mut self, locked: bool, path: &Path) -> Result<TrackedFile, Error> {
+    pub fn set_lock(
+        &mut self,
+        repo: &Repo,
+        locked: bool,
+        path: &Path,
+        message: Option<&str>,
+    ) -> Result<TrackedFile> {
         let lock = |file: &mut TrackedFile| {
             if locked {
                 file.locked = Some(true);
+                file.record_history(repo, "lock".to_string(), message);
             } else {
                 file.locked = None;
+                file.record_history(repo, "unlock".to_string(), message);
             }
         };
-        self.update(path, lock)
+        self.update(repo, path, lock)
+    }
+
+    /// Expand path arguments into recorded files, directories and glob patterns
+    /// are matched against the records instead of the file system
+    pub fn expand(&self, repo: &Repo, paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+        let root_dir = &repo.root_dir;
+        let mut expanded = Vec::new();
+        for path in paths {
+            let path_rel_to_root = get_dir_rel_to_root(root_dir, path)?;
+            let matched: Vec<&PathBuf> = if is_glob(&resolve_path(root_dir, path)) {
+                match glob::Pattern::new(&path_rel_to_root.to_string_lossy()) {
+                    Ok(pattern) => self
+                        .files
+                        .iter()
+                        .map(|file| &file.path)
+                        .filter(|p| pattern.matches_path(p))
+                        .collect(),
+                    Err(_) => Vec::new(),
+                }
+            } else {
+                self.files
+                    .iter()
+                    .map(|file| &file.path)
+                    .filter(|p| p.starts_with(&path_rel_to_root))
+                    .collect()
+            };
+            if matched.is_empty() {
+                // keep the argument so that the caller reports it
+                expanded.push(path.to_path_buf());
+            }
+            expanded.extend(matched.into_iter().map(|p| root_dir.join(p)));
+        }
+        let mut seen = HashSet::new();
+        expanded.retain(|p| seen.insert(p.clone()));
+        Ok(expanded)
     }
 
     /// Check if records contains the file
-    pub fn contains(&self, path: &Path) -> bool {
-        let path = unify(path);
-        let path_rel_to_root = get_path_rel_to_root(&path);
-        self.files.iter().any(|file| file.path == path_rel_to_root)
+    pub fn contains(&self, repo: &Repo, path: &Path) -> bool {
+        get_path_rel_to_root(&repo.root_dir, path).is_ok_and(|path_rel_to_root| {
+            self.files.iter().any(|file| file.path == path_rel_to_root)
+        })
     }
 }
@@ -0,0 +1,626 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::path::Path;
+
+/// Kind of a segment, used to align the source with its translation
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Kind {
+    Heading(usize),
+    /// Fenced code block with its info string
+    Code(String),
+    List,
+    Quote,
+    Table,
+    Paragraph,
+}
+
+/// A segment of text separated by blank lines. Markdown is split into blocks,
+/// a fenced code block is always one block, other text is split into paragraphs
+#[derive(Debug)]
+pub(crate) struct Block<'a> {
+    /// Text of the block with the blank lines after it
+    pub raw: &'a str,
+    pub kind: Kind,
+    /// Line number of the first line of the raw text, starting from 1
+    pub line: usize,
+}
+
+impl Block<'_> {
+    /// Text of the block without the surrounding blank lines
+    pub fn text(&self) -> &str {
+        self.raw.trim()
+    }
+
+    pub fn is_code(&self) -> bool {
+        matches!(self.kind, Kind::Code(_))
+    }
+
+    /// First and last line of the text without the surrounding blank lines
+    pub fn lines(&self) -> (usize, usize) {
+        let leading = self.raw.lines().take_while(|l| l.trim().is_empty()).count();
+        let first = self.line + leading;
+        (first, first + self.text().lines().count().max(1) - 1)
+    }
+}
+
+/// An edit turning one list into another
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Op {
+    Equal(usize, usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+/// Check if a file is split as Markdown, other files are split into plain paragraphs
+pub fn is_markdown(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ["md", "markdown", "mdx"].contains(&ext.to_lowercase().as_str()))
+}
+
+/// Get the kind of a Markdown block from its first line
+fn kind_of(text: &str) -> Kind {
+    let first = text.trim_start().lines().next().unwrap_or_default();
+    let level = first.chars().take_while(|c| *c == '#').count();
+    if first.starts_with("```") || first.starts_with("~~~") {
+        Kind::Code(first.trim_start_matches(['`', '~']).trim().to_string())
+    } else if (1..=6).contains(&level) && first[level..].starts_with(' ') {
+        Kind::Heading(level)
+    } else if first.starts_with(['-', '*', '+']) && first[1..].starts_with(' ')
+        || first
+            .split_once(". ")
+            .is_some_and(|(n, _)| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+    {
+        Kind::List
+    } else if first.starts_with('>') {
+        Kind::Quote
+    } else if first.starts_with('|') {
+        Kind::Table
+    } else {
+        Kind::Paragraph
+    }
+}
+
+/// Split text into blocks, the blocks concatenated are the text
+pub(crate) fn split_blocks(text: &str, markdown: bool) -> Vec<Block<'_>> {
+    let mut blocks = Vec::new();
+    let mut push = |raw, line| {
+        blocks.push(Block {
+            raw,
+            kind: if markdown {
+                kind_of(raw)
+            } else {
+                Kind::Paragraph
+            },
+            line,
+        })
+    };
+    let (mut start, mut pos) = (0, 0);
+    let (mut start_line, mut line_no) = (1, 1);
+    let (mut has_content, mut trailing) = (false, false);
+    let mut fence: Option<&str> = None;
+    for line in text.split_inclusive('\n') {
+        let trimmed = line.trim();
+        if let Some(marker) = fence {
+            if trimmed.starts_with(marker) {
+                fence = None;
+            }
+        } else if trimmed.is_empty() {
+            trailing = has_content;
+        } else {
+            if trailing {
+                push(&text[start..pos], start_line);
+                (start, start_line) = (pos, line_no);
+                trailing = false;
+            }
+            has_content = true;
+            if markdown {
+                fence = ["```", "~~~"]
+                    .into_iter()
+                    .find(|marker| trimmed.starts_with(marker));
+            }
+        }
+        pos += line.len();
+        line_no += 1;
+    }
+    if start < text.len() {
+        push(&text[start..], start_line);
+    }
+    blocks
+}
+
+/// Diff two lists by their longest common subsequence
+pub(crate) fn diff<T, U, F>(a: &[T], b: &[U], eq: F) -> Vec<Op>
+where
+    F: Fn(&T, &U) -> bool,
+{
+    let (n, m) = (a.len(), b.len());
+    // lcs[i][j] is the length of the longest common subsequence of a[i..] and b[j..]
+    let mut lcs = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if eq(&a[i], &b[j]) {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut ops = Vec::new();
+    while i < n || j < m {
+        if i < n && j < m && eq(&a[i], &b[j]) {
+            ops.push(Op::Equal(i, j));
+            (i, j) = (i + 1, j + 1);
+        } else if j < m && (i == n || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            ops.push(Op::Insert(j));
+            j += 1;
+        } else {
+            ops.push(Op::Delete(i));
+            i += 1;
+        }
+    }
+    ops
+}
+
+/// Blocks only in a translation, keyed by the source block before them
+pub(crate) type Extra = HashMap<Option<usize>, Vec<usize>>;
+
+/// Align translation blocks with source blocks by their kinds. Returns the translation
+/// block of each source block, and the blocks only in the translation after each source
+/// block, keyed by `None` for the ones before all source blocks
+pub(crate) fn align_blocks(source: &[Block], translation: &[Block]) -> (Vec<Option<usize>>, Extra) {
+    let mut trans_of = vec![None; source.len()];
+    let mut extra = Extra::new();
+    let mut last_source = None;
+    for op in diff(source, translation, |s, t| s.kind == t.kind) {
+        match op {
+            Op::Equal(i, j) => {
+                trans_of[i] = Some(j);
+                last_source = Some(i);
+            }
+            Op::Delete(i) => last_source = Some(i),
+            Op::Insert(j) => extra.entry(last_source).or_default().push(j),
+        }
+    }
+    (trans_of, extra)
+}
+
+/// Hash of a segment text, 64-bit FNV-1a so that it is stable across builds
+fn hash(text: &str) -> String {
+    let hash = text.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    });
+    format!("{hash:016x}")
+}
+
+/// A source segment aligned with the lines of its translation,
+/// stored in the records as `"<hash> <first>-<last>"`, or `"<hash> -"` if not translated
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub struct AlignedSegment {
+    /// Hash of the source segment text
+    pub source: String,
+    /// First and last line of the translation
+    pub trans: Option<(usize, usize)>,
+}
+
+impl Display for AlignedSegment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.trans {
+            Some((first, last)) => write!(f, "{} {first}-{last}", self.source),
+            None => write!(f, "{} -", self.source),
+        }
+    }
+}
+
+impl From<AlignedSegment> for String {
+    fn from(segment: AlignedSegment) -> Self {
+        segment.to_string()
+    }
+}
+
+impl TryFrom<String> for AlignedSegment {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let invalid = || format!("invalid segment: {value}");
+        let (source, trans) = value.split_once(' ').ok_or_else(invalid)?;
+        let trans = match trans {
+            "-" => None,
+            lines => {
+                let (first, last) = lines.split_once('-').ok_or_else(invalid)?;
+                Some((
+                    first.parse().map_err(|_| invalid())?,
+                    last.parse().map_err(|_| invalid())?,
+                ))
+            }
+        };
+        Ok(AlignedSegment {
+            source: source.to_string(),
+            trans,
+        })
+    }
+}
+
+/// A translated segment outdated by the latest source
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OutdatedSegment {
+    /// Lines of the translation, `None` if the source segment is new
+    pub trans: Option<(usize, usize)>,
+    /// Lines of the latest source, `None` if the source segment is removed
+    pub source: Option<(usize, usize)>,
+    /// Line of the translation after which a new source segment goes
+    pub after: usize,
+}
+
+/// Align the segments of a source with its translation
+pub fn align(path: &Path, source: &str, translation: &str) -> Vec<AlignedSegment> {
+    let markdown = is_markdown(path);
+    let source_blocks = split_blocks(source, markdown);
+    let trans_blocks = split_blocks(translation, markdown);
+    let (trans_of, _) = align_blocks(&source_blocks, &trans_blocks);
+    source_blocks
+        .iter()
+        .zip(trans_of)
+        .map(|(block, trans)| AlignedSegment {
+            source: hash(block.text()),
+            trans: trans.map(|j| trans_blocks[j].lines()),
+        })
+        .collect()
+}
+
+/// Find the translated segments outdated by the latest source, from a stored alignment
+pub fn outdated(path: &Path, segments: &[AlignedSegment], upstream: &str) -> Vec<OutdatedSegment> {
+    let upstream_blocks = split_blocks(upstream, is_markdown(path));
+    let upstream_hashes: Vec<String> = upstream_blocks.iter().map(|b| hash(b.text())).collect();
+    let mut outdated = Vec::new();
+    let mut after = 0;
+    let (mut removed, mut added) = (Vec::new(), Vec::new());
+    let ops = diff(segments, &upstream_hashes, |s, u| s.source == *u);
+    // a hunk ends at the next equal segment, or at the end
+    for op in ops.into_iter().map(Some).chain([None]) {
+        match op {
+            Some(Op::Delete(i)) => removed.push(i),
+            Some(Op::Insert(j)) => added.push(j),
+            Some(Op::Equal(..)) | None => {
+                // removed segments paired with added ones in a hunk are changed segments,
+                // the segments added after them go after their translation
+                for k in 0..removed.len().max(added.len()) {
+                    let trans = removed.get(k).and_then(|i: &usize| segments[*i].trans);
+                    let source = added.get(k).map(|j: &usize| upstream_blocks[*j].lines());
+                    if trans.is_some() || source.is_some() {
+                        outdated.push(OutdatedSegment {
+                            trans,
+                            source,
+                            after,
+                        });
+                    }
+                    if let Some((_, last)) = trans {
+                        after = last;
+                    }
+                }
+                removed.clear();
+                added.clear();
+                if let Some(Op::Equal(i, _)) = op {
+                    after = segments[i].trans.map_or(after, |(_, last)| last);
+                }
+            }
+        }
+    }
+    outdated
+}
+
+/// Lines of a translation corresponding to changed lines of its source
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum MappedLines {
+    /// First and last translated line
+    Lines(usize, usize),
+    /// New text goes after this line of the translation, 0 for the beginning
+    After(usize),
+}
+
+impl Display for MappedLines {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MappedLines::Lines(first, last) if first == last => write!(f, "line {first}"),
+            MappedLines::Lines(first, last) => write!(f, "lines {first}-{last}"),
+            MappedLines::After(line) => write!(f, "after line {line}"),
+        }
+    }
+}
+
+/// First and last line of a text, starting from 1
+type Lines = (usize, usize);
+
+/// Map of source lines to translation lines, by the structural alignment of their segments
+#[derive(Debug, Clone)]
+pub struct LineMap {
+    /// First and last line of each source block with the blank lines after it,
+    /// and the lines of its translation
+    blocks: Vec<(Lines, Option<Lines>)>,
+}
+
+impl LineMap {
+    /// Align a source with its translation
+    pub fn new(path: &Path, source: &str, translation: &str) -> LineMap {
+        let markdown = is_markdown(path);
+        let source_blocks = split_blocks(source, markdown);
+        let trans_blocks = split_blocks(translation, markdown);
+        let (trans_of, _) = align_blocks(&source_blocks, &trans_blocks);
+        let blocks = source_blocks
+            .iter()
+            .zip(trans_of)
+            .map(|(block, trans)| {
+                let span = (
+                    block.line,
+                    block.line + block.raw.lines().count().max(1) - 1,
+                );
+                (span, trans.map(|j| trans_blocks[j].lines()))
+            })
+            .collect();
+        LineMap { blocks }
+    }
+
+    /// Map changed source lines to the translated lines
+    pub fn lines(&self, first: usize, last: usize) -> MappedLines {
+        let translated: Vec<Lines> = self
+            .blocks
+            .iter()
+            .filter(|((start, end), _)| *start <= last && first <= *end)
+            .filter_map(|(_, trans)| *trans)
+            .collect();
+        match (translated.first(), translated.last()) {
+            (Some((first, _)), Some((_, last))) => MappedLines::Lines(*first, *last),
+            _ => self.after(first.saturating_sub(1)),
+        }
+    }
+
+    /// Map a source line to the translated line text inserted after it goes after
+    pub fn after(&self, line: usize) -> MappedLines {
+        let after = self
+            .blocks
+            .iter()
+            .take_while(|((start, _), _)| *start <= line)
+            .filter_map(|(_, trans)| *trans)
+            .last()
+            .map_or(0, |(_, last)| last);
+        MappedLines::After(after)
+    }
+
+    /// Annotate each hunk header of a unified diff of the source with the translated
+    /// lines of `name`, the annotation follows the header so the diff stays applicable
+    pub fn map_diff(&self, diff: &str, name: &str) -> String {
+        let mut out = String::new();
+        let mut hunk: Vec<&str> = Vec::new();
+        let flush = |hunk: &mut Vec<&str>, out: &mut String| {
+            if let Some(header) = hunk.first() {
+                let mapped = self.map_hunk(hunk);
+                match mapped {
+                    Some(mapped) => out.push_str(&format!("{header} [{name}: {mapped}]\n")),
+                    None => out.push_str(&format!("{header}\n")),
+                }
+                hunk[1..].iter().for_each(|line| {
+                    out.push_str(line);
+                    out.push('\n');
+                });
+            }
+            hunk.clear();
+        };
+        for line in diff.lines() {
+            if line.starts_with("@@") {
+                flush(&mut hunk, &mut out);
+                hunk.push(line);
+            } else if hunk.is_empty() {
+                out.push_str(line);
+                out.push('\n');
+            } else {
+                hunk.push(line);
+            }
+        }
+        flush(&mut hunk, &mut out);
+        out
+    }
+
+    /// Map the changed lines of a hunk, `None` if the header cannot be parsed
+    fn map_hunk(&self, hunk: &[&str]) -> Option<MappedLines> {
+        // the header is `@@ -<start>[,<count>] +<start>[,<count>] @@`
+        let old = hunk.first()?.split(' ').nth(1)?.strip_prefix('-')?;
+        let (start, count) = old.split_once(',').unwrap_or((old, "1"));
+        let (start, count): (usize, usize) = (start.parse().ok()?, count.parse().ok()?);
+        // an empty range starts after its line
+        let mut line = if count == 0 { start + 1 } else { start };
+        // span of the removed lines, and of the lines the added ones are inserted after
+        let (mut removed, mut inserted): (Option<Lines>, Option<Lines>) = (None, None);
+        let widen = |span: Option<Lines>, line| match span {
+            Some((first, last)) => Some((first.min(line), last.max(line))),
+            None => Some((line, line)),
+        };
+        for body in &hunk[1..] {
+            match body.chars().next() {
+                Some('-') => {
+                    removed = widen(removed, line);
+                    line += 1;
+                }
+                Some('+') => inserted = widen(inserted, line - 1),
+                Some('\\') => {}
+                _ => line += 1,
+            }
+        }
+        match (removed, inserted) {
+            (None, Some((first, last))) if first == last => Some(self.after(first)),
+            (None, Some((first, last))) => Some(self.lines(first + 1, last)),
+            (Some((first, last)), None) => Some(self.lines(first, last)),
+            (Some(removed), Some(inserted)) => {
+                Some(self.lines(removed.0.min(inserted.0 + 1), removed.1.max(inserted.1)))
+            }
+            (None, None) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    const SOURCE: &str =
+        "# Title\n\nFirst paragraph\nof two lines.\n\n```sh\nmake\n\nmake install\n```\n\n- item\n";
+
+    #[test]
+    fn split_markdown_blocks() {
+        let blocks = split_blocks(SOURCE, true);
+        let kinds: Vec<Kind> = blocks.iter().map(|b| b.kind.clone()).collect();
+        assert_eq!(
+            kinds,
+            [
+                Kind::Heading(1),
+                Kind::Paragraph,
+                Kind::Code("sh".to_string()),
+                Kind::List
+            ]
+        );
+        assert_eq!(blocks.iter().map(|b| b.raw).collect::<String>(), SOURCE);
+        let lines: Vec<(usize, usize)> = blocks.iter().map(|b| b.lines()).collect();
+        assert_eq!(lines, [(1, 1), (3, 4), (6, 10), (12, 12)]);
+    }
+
+    #[test]
+    fn split_plain_paragraphs() {
+        let blocks = split_blocks("# not a heading\n\n```\na\n\nb\n", false);
+        let texts: Vec<&str> = blocks.iter().map(|b| b.text()).collect();
+        assert_eq!(texts, ["# not a heading", "```\na", "b"]);
+        assert!(blocks.iter().all(|b| b.kind == Kind::Paragraph));
+    }
+
+    #[test]
+    fn diff_lists() {
+        let ops = diff(&["a", "b", "c"], &["a", "x", "c", "d"], |a, b| a == b);
+        assert_eq!(
+            ops,
+            [
+                Op::Equal(0, 0),
+                Op::Insert(1),
+                Op::Delete(1),
+                Op::Equal(2, 2),
+                Op::Insert(3)
+            ]
+        );
+    }
+
+    #[test]
+    fn align_blocks_by_kind() {
+        let source = split_blocks("# A\n\nText.\n\n- item\n", true);
+        let translation = split_blocks("# 甲\n\n> 注\n\n文字。\n", true);
+        let (trans_of, extra) = align_blocks(&source, &translation);
+        assert_eq!(trans_of, [Some(0), Some(2), None]);
+        assert_eq!(extra.get(&Some(0)), Some(&vec![1]));
+    }
+
+    #[test]
+    fn aligned_segment_round_trip() {
+        for text in ["0123456789abcdef 3-5", "0123456789abcdef -"] {
+            let segment = AlignedSegment::try_from(text.to_string()).unwrap();
+            assert_eq!(String::from(segment), text);
+        }
+        assert!(AlignedSegment::try_from("0123456789abcdef 3".to_string()).is_err());
+        assert!(AlignedSegment::try_from("0123456789abcdef".to_string()).is_err());
+    }
+
+    #[test]
+    fn outdated_segments() {
+        let path = Path::new("doc.md");
+        let translation = "# 标题\n\n第一段。\n\n第二段。\n";
+        let segments = align(path, "# Title\n\nFirst.\n\nSecond.\n", translation);
+        assert_eq!(segments[1].trans, Some((3, 3)));
+
+        // a changed segment followed by a new one, the new one goes after the changed one
+        let outdated = outdated(
+            path,
+            &segments,
+            "# Title\n\nFirst, changed.\n\nNew.\n\nSecond.\n",
+        );
+        assert_eq!(
+            outdated,
+            [
+                OutdatedSegment {
+                    trans: Some((3, 3)),
+                    source: Some((3, 3)),
+                    after: 1,
+                },
+                OutdatedSegment {
+                    trans: None,
+                    source: Some((5, 5)),
+                    after: 3,
+                },
+            ]
+        );
+    }
+
+    fn line_map() -> LineMap {
+        LineMap::new(
+            Path::new("doc.md"),
+            "# Title\n\nFirst.\n\nSecond.\n",
+            "# 标题\n\n第一段。\n\n第二段。\n",
+        )
+    }
+
+    fn map_header(diff: &str) -> String {
+        line_map()
+            .map_diff(diff, "t.md")
+            .lines()
+            .nth(2)
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn map_inserted_lines() {
+        // a new paragraph after the first one goes after its translation
+        let diff = "--- a/doc.md\n+++ b/doc.md\n@@ -4,0 +5,2 @@\n+New.\n+\n";
+        assert_eq!(map_header(diff), "@@ -4,0 +5,2 @@ [t.md: after line 3]");
+        // at the beginning of the file
+        let diff = "--- a/doc.md\n+++ b/doc.md\n@@ -0,0 +1,2 @@\n+New.\n+\n";
+        assert_eq!(map_header(diff), "@@ -0,0 +1,2 @@ [t.md: after line 0]");
+    }
+
+    #[test]
+    fn map_removed_lines() {
+        let diff = "--- a/doc.md\n+++ b/doc.md\n@@ -3,2 +2,0 @@\n-First.\n-\n";
+        assert_eq!(map_header(diff), "@@ -3,2 +2,0 @@ [t.md: line 3]");
+    }
+
+    #[test]
+    fn map_mixed_hunk() {
+        let diff = "--- a/doc.md\n+++ b/doc.md\n@@ -3,3 +3,3 @@\n\
+            -First.\n+First, changed.\n \n-Second.\n+Second, changed.\n\
+            \\ No newline at end of file\n";
+        assert_eq!(map_header(diff), "@@ -3,3 +3,3 @@ [t.md: lines 3-5]");
+    }
+
+    #[test]
+    fn unparseable_hunk_header() {
+        let diff = "--- a/doc.md\n+++ b/doc.md\n@@ -x +1 @@\n-First.\n+Changed.\n";
+        assert_eq!(line_map().map_diff(diff, "t.md"), diff);
+    }
+
+    #[test]
+    fn mapped_diff_applies() {
+        let dir = std::env::temp_dir().join(format!("git-trans-map-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("doc.md"), "# Title\n\nFirst.\n\nSecond.\n").unwrap();
+        let diff = "--- a/doc.md\n+++ b/doc.md\n@@ -3,3 +3,5 @@\n\
+            -First.\n+First, changed.\n+\n+New.\n \n Second.\n";
+        fs::write(dir.join("doc.diff"), line_map().map_diff(diff, "t.md")).unwrap();
+        let applied = std::process::Command::new("git")
+            .args(["apply", "doc.diff"])
+            .current_dir(&dir)
+            .status()
+            .unwrap();
+        let patched = fs::read_to_string(dir.join("doc.md")).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(applied.success());
+        assert_eq!(patched, "# Title\n\nFirst, changed.\n\nNew.\n\nSecond.\n");
+    }
+}
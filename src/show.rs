@@ -0,0 +1,272 @@
+use colored::control::set_override;
+use colored::*;
+use std::io::IsTerminal;
+use std::path::Path;
+
+use git_trans::config::{ColorMode, Config};
+use git_trans::records::CopyState;
+use git_trans::utils::unify;
+use git_trans::{Progress, Records, Repo, Result, TrackedFile};
+
+/// Apply the colour mode to the output
+pub fn apply_color(config: &Config) {
+    match config.color {
+        ColorMode::Auto => set_override(std::io::stdout().is_terminal()),
+        ColorMode::Always => set_override(true),
+        ColorMode::Never => set_override(false),
+    }
+}
+
+/// Show the transitions of the file, oldest first
+pub fn show_history(file: &TrackedFile) {
+    let Some(history) = file.history.as_ref().filter(|h| !h.is_empty()) else {
+        println!("No history of {}.", file.path.display());
+        return;
+    };
+    for entry in history {
+        print!(
+            "{}  {}  {}",
+            entry.datetime.truecolor(128, 128, 128),
+            entry.author.as_deref().unwrap_or("unknown"),
+            entry.action.yellow()
+        );
+        match &entry.message {
+            Some(message) => println!("  {message}"),
+            None => println!(),
+        }
+    }
+}
+
+/// Show file with progress, synced and locked status columns
+fn show_file(config: &Config, file: &TrackedFile) {
+    let prog = file.progress.letter(config);
+    let synced = if file.orphaned == Some(true) {
+        "O".red()
+    } else if file.synced {
+        "S".bright_blue()
+    } else {
+        "-".truecolor(128, 128, 128)
+    };
+    let lock = if file.locked == Some(true) {
+        "L".bright_green()
+    } else {
+        "-".truecolor(128, 128, 128)
+    };
+    println!("{prog}{synced}{lock}\t{}", file.path.display());
+}
+
+/// Show all information known about the file
+pub fn show_info(repo: &Repo, file: &TrackedFile) -> Result<()> {
+    let info = file.info(repo)?;
+    println!("{:<10}{}", "Path:", info.path.display());
+    match (&info.track_date, &info.track_subject) {
+        (Some(date), Some(subject)) => println!(
+            "{:<10}{} ({date})\n{:<10}{subject}",
+            "Track:",
+            info.track_rev.yellow(),
+            ""
+        ),
+        _ => println!("{:<10}{}", "Track:", info.track_rev.yellow()),
+    }
+    match &info.upstream_rev {
+        Some(upstream_rev) => println!(
+            "{:<10}{} ({} commits since track)",
+            "Upstream:",
+            upstream_rev.yellow(),
+            info.commits_since
+        ),
+        None => println!("{:<10}{}", "Upstream:", "missing".red()),
+    }
+    println!("{:<10}{}", "Progress:", info.progress.colored(&repo.config));
+    println!(
+        "{:<10}{}",
+        "Locked:",
+        if info.locked {
+            "Locked".bright_green()
+        } else {
+            "Unlocked".green()
+        }
+    );
+    println!(
+        "{:<10}{}",
+        "Synced:",
+        if info.orphaned {
+            "Orphaned".red()
+        } else if info.synced {
+            "Synced".bright_blue()
+        } else {
+            "Unsynced".blue()
+        }
+    );
+    let copy = match info.copy {
+        CopyState::Missing => "missing".red(),
+        CopyState::Unknown => "unknown".truecolor(128, 128, 128),
+        CopyState::Same => "same as source".red(),
+        CopyState::Differs => "differs from source".green(),
+    };
+    println!("{:<10}{copy}", "Copy:");
+    let blob = |blob: &Option<String>| match blob {
+        Some(blob) => blob.yellow(),
+        None => "missing".red(),
+    };
+    println!("{:<10}{}", "Src blob:", blob(&info.source_blob));
+    println!("{:<10}{}", "Up blob:", blob(&info.upstream_blob));
+    match info.outdated_segments {
+        Some(0) => println!("{:<10}{}", "Segments:", "up to date".green()),
+        Some(count) => println!("{:<10}{}", "Segments:", format!("{count} outdated").red()),
+        None => println!(
+            "{:<10}{}",
+            "Segments:",
+            "not aligned".truecolor(128, 128, 128)
+        ),
+    }
+    let user = |user: &Option<String>| match user {
+        Some(user) => user.normal(),
+        None => "none".truecolor(128, 128, 128),
+    };
+    println!("{:<10}{}", "Assignee:", user(&info.assignee));
+    println!("{:<10}{}", "Reviewer:", user(&info.reviewer));
+    if let Some(requester) = &info.review_requester {
+        println!("{:<10}{requester}", "Asked by:");
+    }
+    if file.history.as_ref().is_some_and(|h| !h.is_empty()) {
+        println!("History:");
+        show_history(file);
+    }
+    Ok(())
+}
+
+/// Show legend of the status columns
+fn show_legend(config: &Config) {
+    let states: Vec<String> = config
+        .workflow
+        .iter()
+        .map(|state| {
+            let prog = Progress::new(&state.name);
+            format!("{}: {}", prog.letter(config), prog.colored(config))
+        })
+        .collect();
+    println!(
+        "{} | {}, {} | {}",
+        states.join(", "),
+        "S: Synced".bright_blue(),
+        "O: Orphaned".red(),
+        "L: Locked".bright_green()
+    );
+}
+
+/// Show all files in records
+pub fn show_all(config: &Config, records: &Records) {
+    if records.files.is_empty() {
+        println!("No files in records.");
+        return;
+    }
+    show_legend(config);
+    for file in records.files.iter() {
+        show_file(config, file);
+    }
+}
+
+/// Show files recorded under a directory relative to the root directory
+pub fn show_dir(config: &Config, records: &Records, dir: &Path, recursive: bool) {
+    let (files, subdirs) = records.in_dir(dir, recursive);
+    if files.is_empty() && subdirs.is_empty() {
+        println!("No files in records under {}.", unify(dir).display());
+        return;
+    }
+    show_legend(config);
+    for file in files {
+        show_file(config, file);
+    }
+    for subdir in subdirs {
+        println!("   \t{}/", subdir.display());
+    }
+}
+
+/// Show files with specific status
+pub fn show_progress(config: &Config, records: &Records, prog: Progress) {
+    let files = records.with_progress(&prog);
+    if files.is_empty() {
+        println!("\nNo files are in the {} status.", prog);
+        return;
+    }
+    for file in files {
+        println!("{}\t{}", prog.colored(config), file.path.display());
+    }
+}
+
+/// Show files synced or unsynced, with the count of outdated segments of unsynced files
+pub fn show_synced(repo: &Repo, records: &Records, synced: bool) {
+    let files = records.with_synced(synced);
+    if files.is_empty() {
+        println!(
+            "\nNo files are {}.",
+            if synced { "Synced" } else { "Unsynced" }
+        );
+        return;
+    }
+    for file in files {
+        let outdated = match file.outdated_segments(repo) {
+            Some(outdated) if !file.synced => {
+                format!(" ({} segments outdated)", outdated.len())
+            }
+            _ => String::new(),
+        };
+        println!(
+            "{}\t{}{outdated}",
+            if file.synced {
+                "Synced".bright_blue()
+            } else {
+                "Unsynced".blue()
+            },
+            file.path.display()
+        );
+    }
+}
+
+/// Show files locked or unlocked
+pub fn show_locked(records: &Records, locked: bool) {
+    let files = records.with_locked(locked);
+    if files.is_empty() {
+        println!(
+            "\nNo files are {}.",
+            if locked { "Locked" } else { "Unlocked" }
+        );
+        return;
+    }
+    for file in files {
+        println!(
+            "{}\t{}",
+            if locked {
+                "Locked".bright_green()
+            } else {
+                "Unlocked".green()
+            },
+            file.path.display()
+        );
+    }
+}
+
+/// Show files deleted or renamed upstream
+pub fn show_orphaned(records: &Records) {
+    let files = records.with_orphaned();
+    if files.is_empty() {
+        println!("\nNo files are Orphaned.");
+        return;
+    }
+    for file in files {
+        println!("{}\t{}", "Orphaned".red(), file.path.display());
+    }
+}
+
+/// Show files in states unknown to the workflow
+pub fn show_unknown(config: &Config, records: &Records) {
+    let files = records.with_unknown_progress(config);
+    if files.is_empty() {
+        println!("\nNo files are in unknown states.");
+        return;
+    }
+    for file in files {
+        println!("{}\t{}", file.progress.colored(config), file.path.display());
+    }
+}
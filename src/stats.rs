@@ -0,0 +1,257 @@
+use colored::Colorize;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::ops::AddAssign;
+use std::path::{Path, PathBuf};
+
+use crate::config::Config;
+use crate::errors::Result;
+use crate::git::{count_changed_lines_since, get_file_at_rev};
+use crate::project::Repo;
+use crate::records::{Progress, TrackedFile};
+
+/// Size of the sources of some files at their track revisions
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct Tally {
+    pub files: usize,
+    pub words: usize,
+    /// Characters other than whitespace
+    pub chars: usize,
+    pub lines: usize,
+    /// Files not synced with their latest source
+    pub unsynced: usize,
+    /// Lines added and deleted upstream since the track revisions of the unsynced files
+    pub backlog: usize,
+}
+
+impl Tally {
+    /// Tally of one file, counting its source at the track revision
+    fn of(file: &TrackedFile, root_dir: &Path) -> Tally {
+        let source = get_file_at_rev(root_dir, &file.path, &file.track_rev)
+            .map(|source| String::from_utf8_lossy(&source).to_string())
+            .unwrap_or_default();
+        let backlog = if file.synced {
+            0
+        } else {
+            count_changed_lines_since(root_dir, &file.path, &file.track_rev).unwrap_or(0)
+        };
+        Tally {
+            files: 1,
+            words: source.split_whitespace().count(),
+            chars: source.chars().filter(|c| !c.is_whitespace()).count(),
+            lines: source.lines().count(),
+            unsynced: usize::from(!file.synced),
+            backlog,
+        }
+    }
+}
+
+impl AddAssign for Tally {
+    fn add_assign(&mut self, other: Tally) {
+        self.files += other.files;
+        self.words += other.words;
+        self.chars += other.chars;
+        self.lines += other.lines;
+        self.unsynced += other.unsynced;
+        self.backlog += other.backlog;
+    }
+}
+
+/// Tallies of a group of files by progress, in the order of the workflow
+#[derive(Debug, Clone, PartialEq)]
+pub struct Group {
+    /// Directory or assignee of the files, empty for all the files
+    pub name: String,
+    pub progress: Vec<(Progress, Tally)>,
+}
+
+impl Group {
+    /// An empty group with every state of the workflow
+    fn new(config: &Config, name: &str) -> Group {
+        Group {
+            name: name.to_string(),
+            progress: config
+                .workflow
+                .iter()
+                .map(|state| (Progress::new(&state.name), Tally::default()))
+                .collect(),
+        }
+    }
+
+    /// Add a file to the tally of its progress, states unknown to the workflow come last
+    fn add(&mut self, progress: &Progress, tally: Tally) {
+        match self.progress.iter_mut().find(|(prog, _)| prog == progress) {
+            Some((_, sum)) => *sum += tally,
+            None => self.progress.push((progress.clone(), tally)),
+        }
+    }
+
+    /// Tally of all the files in the group
+    pub fn total(&self) -> Tally {
+        let mut total = Tally::default();
+        for (_, tally) in self.progress.iter() {
+            total += *tally;
+        }
+        total
+    }
+
+    /// Percentage of the group in a progress, weighted by source characters,
+    /// or by files when the sources are empty
+    pub fn percent(&self, progress: &Progress) -> f64 {
+        let total = self.total();
+        let tally = self
+            .progress
+            .iter()
+            .find(|(prog, _)| prog == progress)
+            .map(|(_, tally)| *tally)
+            .unwrap_or_default();
+        match (total.chars, total.files) {
+            (0, 0) => 0.0,
+            (0, files) => 100.0 * tally.files as f64 / files as f64,
+            (chars, _) => 100.0 * tally.chars as f64 / chars as f64,
+        }
+    }
+}
+
+/// Progress statistics of the files of a language, in total,
+/// by directory and by assignee
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stats {
+    pub lang: String,
+    /// Progress of finished files, the last state of the workflow
+    pub finished: Progress,
+    pub total: Group,
+    /// Groups of the directories at the given depth, `.` for files at the root
+    pub dirs: Vec<Group>,
+    /// Groups of the assignees, `none` for unassigned files
+    pub assignees: Vec<Group>,
+}
+
+impl Stats {
+    /// Collect the statistics of files, grouping them by the leading
+    /// `depth` components of their directories
+    pub fn collect(repo: &Repo, lang: &str, files: &[TrackedFile], depth: usize) -> Result<Stats> {
+        let config = &repo.config;
+        let mut total = Group::new(config, "");
+        let mut dirs: BTreeMap<String, Group> = BTreeMap::new();
+        let mut assignees: BTreeMap<String, Group> = BTreeMap::new();
+        for file in files {
+            let tally = Tally::of(file, &repo.root_dir);
+            let dir = dir_at_depth(&file.path, depth);
+            let assignee = file.assignee.as_deref().unwrap_or("none");
+            total.add(&file.progress, tally);
+            dirs.entry(dir.clone())
+                .or_insert_with(|| Group::new(config, &dir))
+                .add(&file.progress, tally);
+            assignees
+                .entry(assignee.to_string())
+                .or_insert_with(|| Group::new(config, assignee))
+                .add(&file.progress, tally);
+        }
+        Ok(Stats {
+            lang: lang.to_string(),
+            finished: config.last_progress(),
+            total,
+            dirs: dirs.into_values().collect(),
+            assignees: assignees.into_values().collect(),
+        })
+    }
+
+    /// Percentage of a group in the last state of the workflow
+    pub fn complete(&self, group: &Group) -> f64 {
+        group.percent(&self.finished)
+    }
+}
+
+/// Leading components of the directory of a file, `.` for files at the root
+fn dir_at_depth(path: &Path, depth: usize) -> String {
+    let dir: PathBuf = path
+        .parent()
+        .map(|dir| dir.components().take(depth).collect())
+        .unwrap_or_default();
+    if dir.as_os_str().is_empty() {
+        ".".to_string()
+    } else {
+        dir.display().to_string()
+    }
+}
+
+/// Write a table of groups, one line for each with its completion and backlog
+fn write_groups(
+    f: &mut std::fmt::Formatter<'_>,
+    stats: &Stats,
+    title: &str,
+    groups: &[Group],
+) -> std::fmt::Result {
+    let width = groups
+        .iter()
+        .map(|group| group.name.chars().count())
+        .chain([title.len()])
+        .max()
+        .unwrap_or_default();
+    writeln!(
+        f,
+        "{title:<width$}  {:>6}  {:>8}  {:>9}  {:>7}  {:>8}  {:>8}  {:>7}",
+        "Files", "Words", "Chars", "Lines", "Complete", "Unsynced", "Backlog"
+    )?;
+    for group in groups {
+        let total = group.total();
+        writeln!(
+            f,
+            "{:<width$}  {:>6}  {:>8}  {:>9}  {:>7}  {:>7.1}%  {:>8}  {:>7}",
+            group.name,
+            total.files,
+            total.words,
+            total.chars,
+            total.lines,
+            stats.complete(group),
+            total.unsynced,
+            total.backlog
+        )?;
+    }
+    Ok(())
+}
+
+impl Display for Stats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let total = self.total.total();
+        writeln!(
+            f,
+            "{}: {:.1}% complete, weighted by source characters",
+            self.lang.bold(),
+            self.complete(&self.total)
+        )?;
+        writeln!(
+            f,
+            "\n{:<12}  {:>6}  {:>8}  {:>9}  {:>7}  {:>7}",
+            "Progress", "Files", "Words", "Chars", "Lines", "Percent"
+        )?;
+        for (prog, tally) in self.total.progress.iter() {
+            writeln!(
+                f,
+                "{:<12}  {:>6}  {:>8}  {:>9}  {:>7}  {:>6.1}%",
+                prog.0,
+                tally.files,
+                tally.words,
+                tally.chars,
+                tally.lines,
+                self.total.percent(prog)
+            )?;
+        }
+        writeln!(
+            f,
+            "{:<12}  {:>6}  {:>8}  {:>9}  {:>7}",
+            "Total", total.files, total.words, total.chars, total.lines
+        )?;
+        writeln!(
+            f,
+            "\nUnsynced: {} files, {} lines changed upstream",
+            total.unsynced, total.backlog
+        )?;
+        writeln!(f)?;
+        write_groups(f, self, "Directory", &self.dirs)?;
+        writeln!(f)?;
+        write_groups(f, self, "Assignee", &self.assignees)
+    }
+}
@@ -1,11 +1,16 @@
 use core::convert::AsRef;
+use std::collections::HashSet;
+use std::env;
 use std::fs::{self, File, OpenOptions};
-use std::io::{Error, ErrorKind, Result, Write};
-use std::path::{Path, PathBuf, StripPrefixError};
+use std::io::{Error, ErrorKind};
+use std::path::{Component, Path, PathBuf, StripPrefixError};
 
+use glob::Pattern;
 use log::debug;
 
-use crate::git::get_root_dir;
+use crate::errors::{GitTransError, Result};
+use crate::git::{get_blob, get_git_path, list_tracked_files, reset};
+use crate::project::Repo;
 
 /// # File handling functions
 ///
@@ -15,98 +20,210 @@ pub fn create_file_with_dirs<P: AsRef<Path>>(path: P) -> Result<File> {
         fs::create_dir_all(parent)?;
     }
     if path.as_ref().exists() {
-        return Err(Error::new(ErrorKind::AlreadyExists, "file already exists"));
+        return Err(Error::new(ErrorKind::AlreadyExists, "file already exists").into());
     }
-    OpenOptions::new().write(true).truncate(true).open(path)
+    Ok(OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path)?)
 }
 
 /// Copy a file to a new location with all the necessary directories
 pub fn copy_file<P: AsRef<Path>, Q: AsRef<Path>>(from: P, to: Q, overwrite: bool) -> Result<u64> {
     if !overwrite && to.as_ref().exists() {
-        return Err(Error::new(ErrorKind::AlreadyExists, "file already exists"));
+        return Err(Error::new(ErrorKind::AlreadyExists, "file already exists").into());
     }
 
     if let Some(parent) = to.as_ref().parent() {
         fs::create_dir_all(parent)?;
     }
-    fs::copy(from, to)
+    Ok(fs::copy(from, to)?)
 }
 
 /// Copy a file to the .trans directory
-pub fn copy_file_to_trans<P: AsRef<Path>>(from: P) -> Result<u64> {
-    let path_rel_to_root = get_path_rel_to_root(&PathBuf::from(from.as_ref()));
-    let to = get_trans_dir().join(path_rel_to_root);
-    println!("from: {:?}, to: {:?}", from.as_ref(), to);
+pub fn copy_file_to_trans<P: AsRef<Path>>(repo: &Repo, from: P) -> Result<u64> {
+    let path_rel_to_root = get_path_rel_to_root(&repo.root_dir, from.as_ref())?;
+    let to = repo.trans_dir().join(path_rel_to_root);
+    debug!("from: {:?}, to: {:?}", from.as_ref(), to);
     copy_file(from, to, false)
 }
 
-/// Write content to a file
-pub fn write_diff_file_to_trans<P: AsRef<Path>>(to: P, content: &str) -> Result<()> {
-    let path_rel_to_root = get_path_rel_to_root(&PathBuf::from(to.as_ref()));
-    let mut to = get_trans_dir().join(path_rel_to_root);
-    if let Some(fname) = to.file_name().and_then(|s| s.to_str()) {
-        to.set_file_name(format!("{}.diff", fname));
+/// Get the diff file of a file in the .trans directory, the path is relative to the root directory
+pub fn get_diff_file(trans_dir: &Path, path_rel_to_root: &Path) -> PathBuf {
+    let mut diff_file = trans_dir.join(path_rel_to_root);
+    if let Some(fname) = diff_file.file_name().and_then(|s| s.to_str()) {
+        diff_file.set_file_name(format!("{}.diff", fname));
     }
-    let mut file = create_file_with_dirs(to)?;
-    file.write_all(content.as_bytes())?;
+    diff_file
+}
+
+/// Write the diff file of a file to the .trans directory, an existing one is overwritten
+pub fn write_diff_file_to_trans<P: AsRef<Path>>(repo: &Repo, to: P, content: &str) -> Result<()> {
+    let to = get_diff_file(
+        &repo.trans_dir(),
+        &get_path_rel_to_root(&repo.root_dir, to.as_ref())?,
+    );
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(to, content)?;
     Ok(())
 }
 
-/// Copy files in .trans folder to root directory
-pub fn cover() -> Result<u64> {
-    let from = get_trans_dir();
-    let to_root = get_root_dir().unwrap();
-    // recursively copy everything under .trans into root, skipping the records file
-    copy_dir_recursive(&from, &to_root, &from)
-}
-
-// TODO: 改成按照 records 记录来覆盖
-/// Recursively walk a source directory and copy all files to the destination root,
-/// preserving the tree structure. `base` is the top of the recursion and is used
-/// to compute the relative path for each entry. The function returns the number of
-/// files copied. Existing files are always overwritten.
-fn copy_dir_recursive(src: &Path, dest_root: &Path, base: &Path) -> Result<u64> {
-    let mut count = 0;
-    for entry in fs::read_dir(src)? {
-        let entry = entry?;
-        let path = entry.path();
-
-        // skip the records file at the top level
-        if path.file_name().and_then(|n| n.to_str()) == Some("records.toml") {
-            continue;
+/// Remove the diff file of a file from the .trans directory if it exists
+pub fn remove_diff_file_from_trans<P: AsRef<Path>>(repo: &Repo, path: P) -> Result<()> {
+    let diff_file = get_diff_file(
+        &repo.trans_dir(),
+        &get_path_rel_to_root(&repo.root_dir, path.as_ref())?,
+    );
+    if diff_file.is_file() {
+        fs::remove_file(diff_file)?;
+    }
+    Ok(())
+}
+
+/// Move a copy in the .trans directory to a new path, the paths are relative to the root directory
+pub fn move_in_trans(trans_dir: &Path, from: &Path, to: &Path) -> Result<()> {
+    let (from, to) = (trans_dir.join(from), trans_dir.join(to));
+    if !from.exists() {
+        return Ok(());
+    }
+    if to.exists() {
+        return Err(Error::new(ErrorKind::AlreadyExists, "file already exists").into());
+    }
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::rename(&from, to)?;
+    remove_empty_dirs(&from, trans_dir);
+    Ok(())
+}
+
+/// Remove the directories of a removed file left empty, up to but not including a directory
+pub fn remove_empty_dirs(path: &Path, stop: &Path) {
+    for dir in path.ancestors().skip(1).take_while(|dir| *dir != stop) {
+        if fs::remove_dir(dir).is_err() {
+            break;
         }
+    }
+}
 
-        if path.is_dir() {
-            // recurse into subdirectory
-            count += copy_dir_recursive(&path, dest_root, base)?;
-        } else if path.is_file() {
-            let rel = path.strip_prefix(base).unwrap();
-            let dest = dest_root.join(rel);
-            if let Some(parent) = dest.parent() {
-                fs::create_dir_all(parent)?;
-            }
-            debug!("cover:\nfrom: {:?},\nto: {:?}", path, dest);
-            if let Err(e) = copy_file(&path, &dest, true) {
-                debug!("cover error: {:?}", e);
+/// List the files in a directory recursively, relative to the directory
+pub fn list_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut dirs = vec![PathBuf::new()];
+    while let Some(rel) = dirs.pop() {
+        for entry in fs::read_dir(dir.join(&rel))? {
+            let entry = entry?;
+            let path = rel.join(entry.file_name());
+            if entry.file_type()?.is_dir() {
+                dirs.push(path);
+            } else {
+                files.push(unify(&path));
             }
-            count += 1;
         }
     }
-    Ok(count)
+    files.sort();
+    Ok(files)
 }
 
-/// # Path handling functions
-///
-/// Get the .trans directory
-pub fn get_trans_dir() -> PathBuf {
-    get_root_dir().unwrap().join(".trans")
+/// Get the cover manifest listing the files covered into the root directory,
+/// kept in the git directory so that it is never committed
+pub fn get_cover_manifest(root_dir: &Path) -> Result<PathBuf> {
+    get_git_path(root_dir, "git-trans-cover")
+}
+
+/// Read the covered files from the cover manifest, relative to the root directory
+pub fn read_cover_manifest(root_dir: &Path) -> Result<Vec<PathBuf>> {
+    let manifest = get_cover_manifest(root_dir)?;
+    if !manifest.is_file() {
+        return Ok(Vec::new());
+    }
+    Ok(fs::read_to_string(manifest)?
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(PathBuf::from)
+        .collect())
+}
+
+/// Write the covered files to the cover manifest, the manifest is removed if there are none
+pub fn write_cover_manifest(root_dir: &Path, paths: &[PathBuf]) -> Result<()> {
+    let manifest = get_cover_manifest(root_dir)?;
+    if paths.is_empty() {
+        if manifest.is_file() {
+            fs::remove_file(manifest)?;
+        }
+        return Ok(());
+    }
+    let content: String = paths
+        .iter()
+        .map(|path| format!("{}\n", unify(path).display()))
+        .collect();
+    fs::write(manifest, content)?;
+    Ok(())
 }
 
-/// Get the records.toml file path
-pub fn get_records_toml() -> PathBuf {
-    get_trans_dir().join("records.toml")
+/// Reset the files covered into a working tree to the latest revision and clear the
+/// cover manifest, returns the files reset. A dry run only returns them
+pub fn reset_covered(root_dir: &Path, dry_run: bool) -> Result<Vec<PathBuf>> {
+    let covered = read_cover_manifest(root_dir)?;
+    if dry_run {
+        return Ok(covered);
+    }
+    // files not in the latest revision were added by cover and are removed
+    let (tracked, added): (Vec<PathBuf>, Vec<PathBuf>) = covered
+        .iter()
+        .cloned()
+        .partition(|path| get_blob(root_dir, path, "HEAD").is_some());
+    reset(root_dir, &tracked)?;
+    for path in added.iter().map(|path| root_dir.join(path)) {
+        if path.is_file() {
+            fs::remove_file(&path)?;
+        }
+        remove_empty_dirs(&path, root_dir);
+    }
+    write_cover_manifest(root_dir, &[])?;
+    Ok(covered)
 }
 
+/// Check if a path argument is a glob pattern rather than an existing path
+pub fn is_glob(path: &Path) -> bool {
+    !path.exists() && path.to_str().is_some_and(|p| p.contains(['*', '?', '[']))
+}
+
+/// Expand path arguments into git tracked files. Directories are walked recursively,
+/// ignored files and the .trans folder are skipped, and the files are filtered
+/// by the include and exclude patterns matching the path relative to the root directory
+pub fn expand_tracked_paths(
+    repo: &Repo,
+    paths: &[PathBuf],
+    include: &[Pattern],
+    exclude: &[Pattern],
+) -> Result<Vec<PathBuf>> {
+    let root_dir = &repo.root_dir;
+    let mut expanded = Vec::new();
+    for path in paths.iter().map(|path| resolve_path(root_dir, path)) {
+        get_dir_rel_to_root(root_dir, &path)?;
+        let matched: Vec<PathBuf> = list_tracked_files(root_dir, &path)?
+            .into_iter()
+            .filter(|file| !file.starts_with(&repo.config.trans_dir))
+            .filter(|file| include.is_empty() || include.iter().any(|p| p.matches_path(file)))
+            .filter(|file| !exclude.iter().any(|p| p.matches_path(file)))
+            .collect();
+        if matched.is_empty() && !path.is_dir() && !is_glob(&path) {
+            // keep the file so that the caller reports it
+            expanded.push(path);
+        }
+        expanded.extend(matched.into_iter().map(|file| root_dir.join(file)));
+    }
+    let mut seen = HashSet::new();
+    expanded.retain(|p| seen.insert(p.clone()));
+    Ok(expanded)
+}
+
+/// # Path handling functions
+///
 /// Convert an absolute path to a relative path
 pub fn absolute_to_relative<P: AsRef<Path>, Q: AsRef<Path>>(
     base: P,
@@ -119,15 +236,58 @@ pub fn absolute_to_relative<P: AsRef<Path>, Q: AsRef<Path>>(
 
 /// Convert a path to a unix style path
 pub fn unify(path: &Path) -> PathBuf {
-    PathBuf::from(path.to_str().unwrap().replace("\\", "/"))
+    PathBuf::from(path.to_string_lossy().replace("\\", "/"))
+}
+
+/// Resolve `.` and `..` components of a path without touching the file system,
+/// returns `None` if the path goes above its start
+fn normalize(path: &Path) -> Option<PathBuf> {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    return None;
+                }
+            }
+            other => normalized.push(other),
+        }
+    }
+    Some(normalized)
 }
 
-/// Get the relative path of a file to the root directory
-pub fn get_path_rel_to_root(path: &Path) -> PathBuf {
-    let root_dir: PathBuf = get_root_dir().unwrap();
+/// Resolve a path argument into an absolute path. Relative paths are resolved from the
+/// current directory when it is inside the root directory, otherwise from the root directory
+pub fn resolve_path(root_dir: &Path, path: &Path) -> PathBuf {
     let path = unify(path);
-    unify(
-        &absolute_to_relative::<PathBuf, PathBuf>(root_dir, fs::canonicalize(&path).unwrap())
-            .unwrap(),
-    )
+    if path.is_absolute() {
+        return path;
+    }
+    env::current_dir()
+        .and_then(fs::canonicalize)
+        .ok()
+        .filter(|dir| dir.starts_with(root_dir))
+        .unwrap_or_else(|| root_dir.to_path_buf())
+        .join(path)
+}
+
+/// Get the relative path of a file to the root directory,
+/// files that do not exist are resolved lexically
+pub fn get_path_rel_to_root(root_dir: &Path, path: &Path) -> Result<PathBuf> {
+    match fs::canonicalize(resolve_path(root_dir, path)) {
+        Ok(absolute_path) => absolute_to_relative(root_dir, absolute_path)
+            .map(|p| unify(&p))
+            .map_err(|_| GitTransError::PathOutsideRepo(unify(path))),
+        Err(_) => get_dir_rel_to_root(root_dir, path),
+    }
+}
+
+/// Get the relative path of a directory to the root directory, resolved lexically
+pub fn get_dir_rel_to_root(root_dir: &Path, path: &Path) -> Result<PathBuf> {
+    let outside = || GitTransError::PathOutsideRepo(path.to_path_buf());
+    let path = normalize(&resolve_path(root_dir, path)).ok_or_else(outside)?;
+    absolute_to_relative(root_dir, path)
+        .map(|p| unify(&p))
+        .map_err(|_| outside())
 }